@@ -0,0 +1,160 @@
+// generated from norg-rs types by `tests/schema.rs`, don't edit
+
+export type NorgAST = { 
+/**
+ * format version, bumped on every incompatible change
+ */
+version: number, blocks: Array<NorgBlock>, 
+/**
+ * anchor definitions by their id
+ */
+anchors: { [key in string]?: AnchorDefinitionNode }, 
+/**
+ * ordered by position of the offending definition or reference
+ */
+anchor_issues: Array<AnchorIssue>, 
+/**
+ * problems found while parsing, ordered by position
+ */
+diagnostics: Array<Diagnostic>, };
+
+export type NorgBlock = { "Section": { 
+/**
+ * carryover attributes (`#(this)`) applied to the entire section
+ */
+attrs: Array<Attribute>, level: number, heading: Heading | null, contents: Array<NorgBlock>, range: Range, } } | { "Paragraph": { attrs: Array<Attribute>, inlines: Array<NorgInline>, range: Range, } } | { "UnorderedList": { attrs: Array<Attribute>, level: number, items: Array<ListItem>, range: Range, } } | { "OrderedList": { attrs: Array<Attribute>, level: number, items: Array<ListItem>, range: Range, } } | { "Quote": { attrs: Array<Attribute>, level: number, items: Array<ListItem>, range: Range, } } | { "InfirmTag": { 
+/**
+ * carryover attributes applied to the tag invocation
+ */
+attrs: Array<Attribute>, params: Array<string>, name: string, range: Range, } } | { "CarryoverTag": { params: Array<string>, name: string, target: NorgBlock, 
+/**
+ * covers both the tag line and the target block
+ */
+range: Range, } } | { "RangedTag": { attrs: Array<Attribute>, params: Array<string>, name: string, content: Array<string>, range: Range, } } | { "HorizontalLine": { attrs: Array<Attribute>, range: Range, } } | { "Definition": { attrs: Array<Attribute>, title: Array<NorgInline>, contents: Array<NorgBlock>, range: Range, } } | { "Footnote": { attrs: Array<Attribute>, title: Array<NorgInline>, 
+/**
+ * slug of the title, unique among footnotes. `{^ title}` references match the first
+ * footnote with that title
+ */
+id: string, contents: Array<NorgBlock>, range: Range, } } | { "Table": { attrs: Array<Attribute>, rows: Array<TableRow>, range: Range, } };
+
+export type Heading = { 
+/**
+ * attributes in `* (this) heading` form, applied to the heading only
+ */
+attrs: Array<Attribute>, todo: Todo | null, 
+/**
+ * slug of the heading text, unique within the document
+ */
+id: string, inlines: Array<NorgInline>, range: Range, };
+
+export type ListItem = { attrs: Array<Attribute>, todo: Todo | null, 
+/**
+ * carryover tags applied to this item, innermost first
+ */
+tags: Array<Tag>, contents: Array<NorgBlock>, range: Range, };
+
+export type Todo = { status: TodoStatus, 
+/**
+ * `(< date)`, as written
+ */
+due: string | null, };
+
+export type TodoStatus = "Undone" | "Done" | "Uncertain" | "Urgent" | { "Recurring": string | null } | "Pending" | "OnHold" | "Canceled";
+
+export type TableRow = { 
+/**
+ * row is part of the table header
+ */
+header: boolean, cells: Array<TableCell>, range: Range, };
+
+export type TableCell = { attrs: Array<Attribute>, inlines: Array<NorgInline>, range: Range, };
+
+export type Tag = { name: string, params: Array<string>, range: Range, };
+
+export type NorgInline = { "Text": { text: string, range: Range, } } | { "Special": { text: string, range: Range, } } | { "Escape": { character: string, range: Range, } } | { "Whitespace": { range: Range, } } | { "SoftBreak": { range: Range, } } | { "HardBreak": { range: Range, } } | { "Bold": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Italic": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Underline": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Strikethrough": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Verbatim": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Spoiler": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Superscript": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Subscript": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "InlineMath": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Variable": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "NullModifier": { markup: Array<NorgInline>, attrs: Array<Attribute>, 
+/**
+ * written in free-form (`X| ... |X`)
+ */
+free_form: boolean, range: Range, } } | { "Macro": { name: string, markup: Array<NorgInline> | null, attrs: Array<string> | null, range: Range, } } | { "Link": { target: NorgLinkTarget, markup: Array<NorgInline> | null, attrs: Array<Attribute>, range: Range, } } | { "Anchor": { target: NorgLinkTarget | null, markup: Array<NorgInline>, 
+/**
+ * slug of the markup, shared by anchor definition and its references
+ */
+id: string, attrs: Array<Attribute>, range: Range, } };
+
+export type Attribute = "Blank" | { "Key": string } | { "KeyValue": [string, string] };
+
+export type NorgLinkTarget = { "Local": NorgLinkLocalTarget } | { "App": NorgLinkAppTarget } | { "Raw": string };
+
+export type NorgLinkLocalTarget = { "Uri": string } | { "Scope": Array<NorgLinkScope> };
+
+export type NorgLinkAppTarget = { workspace: string | null, path: string, scopes: Array<NorgLinkScope>, };
+
+export type NorgLinkScope = { "Heading": [number, string] } | { "WikiHeading": string } | { "Footnote": string };
+
+export type AnchorDefinitionNode = { range: Range, target: NorgLinkTarget, };
+
+export type AnchorIssue = { "Duplicate": { id: string, first: Range, second: Range, } } | { "Dangling": { id: string, range: Range, } };
+
+export type Diagnostic = { severity: Severity, message: string, range: Range, 
+/**
+ * syntax node kind this diagnostic is reported on, named after tree-sitter-norg nodes
+ */
+kind: string, 
+/**
+ * source at `range` is missing from the AST, so writing the AST back loses it
+ */
+dropped: boolean, };
+
+export type Severity = "Error" | "Warning";
+
+export type Range = { 
+/**
+ * start byte offset
+ */
+start: number, 
+/**
+ * end byte offset (exclusive)
+ */
+end: number, start_point: Point, end_point: Point, };
+
+export type Point = { row: number, column: number, };
@@ -1,6 +1,16 @@
 use wasm_bindgen::prelude::*;
 use js_sys::Array;
 
+#[wasm_bindgen(typescript_custom_section)]
+const AST_TYPES: &str = include_str!("../ast/norg-ast.d.ts");
+
+#[wasm_bindgen]
+extern "C" {
+    /// serialized `NorgAST`, typed by `ast/norg-ast.d.ts`
+    #[wasm_bindgen(typescript_type = "NorgAST")]
+    pub type NorgAst;
+}
+
 #[wasm_bindgen]
 pub struct Converter {
     inner: norg_rs::export::Exporter,
@@ -17,9 +27,9 @@ impl Converter {
     //     let _ = self.inner.run_janet(code);
     // }
     #[wasm_bindgen]
-    pub fn convert(&mut self, ast: JsValue) -> Result<JsValue, JsValue> {
+    pub fn convert(&mut self, ast: NorgAst) -> Result<JsValue, JsValue> {
         // let ast = norg_rs::parser::parse(document.as_bytes());
-        let ast = serde_wasm_bindgen::from_value(ast)?;
+        let ast = serde_wasm_bindgen::from_value(ast.into())?;
         let (html, ctx) = self.inner.export(norg_rs::export::ExportTarget::Html, ast, None)
             .map_err(|e| JsValue::from_str(&format!("Export failed: {:?}", e)))?;
         let ctx_js = serde_wasm_bindgen::to_value(&ctx)
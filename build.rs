@@ -1,6 +1,11 @@
 use std::process::Command;
 
 fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=janet-src");
+    if std::env::var_os("CARGO_FEATURE_JANET").is_none() {
+        return;
+    }
     let output = Command::new("janet")
         .arg("-c")
         .arg("janet-src/stdlib.janet")
@@ -10,6 +15,4 @@ fn main() {
     if !output.status.success() {
         panic!("janet command failed: {}", String::from_utf8_lossy(&output.stderr));
     }
-    println!("cargo::rerun-if-changed=build.rs");
-    println!("cargo::rerun-if-changed=janet-src");
 }
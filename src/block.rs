@@ -1,77 +1,730 @@
+#[cfg(feature = "janet")]
 use janetrs::{
     Janet, JanetConversionError, JanetFunction, JanetKeyword, JanetString, JanetStruct, JanetType,
     TaggedJanet,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::inline::{Attribute, NorgInline};
+#[cfg(feature = "janet")]
+use crate::slug::{markup_text, slugify};
+use crate::{
+    inline::{Attribute, NorgInline},
+    parser::Range,
+};
 
+// `S` is the type of source text, `&str` slices in [`crate::borrowed`] ASTs
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub enum NorgBlock {
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "NorgBlock"),
+    ts(concrete(S = String))
+)]
+pub enum NorgBlock<S = String> {
     Section {
+        /// carryover attributes (`#(this)`) applied to the entire section
         attrs: Vec<Attribute>,
         level: u16,
-        // TODO: use Heading type instead
-        // so that attributes in `* (this) heading` form applied to the heading
-        // instead of entire section.
-        // Section's attributes can only be applied with `#(this)` syntax
-        heading: Option<Vec<NorgInline>>,
+        heading: Option<Heading<S>>,
         contents: Vec<Self>,
+        range: Range,
     },
     Paragraph {
         attrs: Vec<Attribute>,
-        inlines: Vec<NorgInline>,
+        inlines: Vec<NorgInline<S>>,
+        range: Range,
     },
     UnorderedList {
         attrs: Vec<Attribute>,
         level: u16,
-        items: Vec<ListItem>,
+        items: Vec<ListItem<S>>,
+        range: Range,
     },
     OrderedList {
         attrs: Vec<Attribute>,
         level: u16,
-        items: Vec<ListItem>,
+        items: Vec<ListItem<S>>,
+        range: Range,
     },
     Quote {
         attrs: Vec<Attribute>,
         level: u16,
-        items: Vec<ListItem>,
+        items: Vec<ListItem<S>>,
+        range: Range,
     },
     InfirmTag {
-        // TODO: consider rename to attrs
-        params: Option<String>,
-        name: String,
+        /// carryover attributes applied to the tag invocation
+        attrs: Vec<Attribute>,
+        params: Vec<String>,
+        name: S,
+        range: Range,
     },
     // TODO: how to parse this...
     CarryoverTag {
-        params: Option<String>,
-        name: String,
+        params: Vec<String>,
+        name: S,
         target: Box<Self>,
+        /// covers both the tag line and the target block
+        range: Range,
     },
     RangedTag {
-        params: Option<String>,
-        name: String,
-        content: Vec<String>,
+        attrs: Vec<Attribute>,
+        params: Vec<String>,
+        name: S,
+        content: Vec<S>,
+        range: Range,
     },
     // TODO: do I really need this type in rust?
+    #[cfg(feature = "janet")]
     #[serde(skip)]
     Embed {
         attrs: Vec<Attribute>,
         // TODO: switch to HashMap<JanetKeyword, JanetFunction> instead
         // to check if "embed" support specific target language
         export: JanetFunction<'static>,
+        range: Range,
     },
     HorizontalLine {
         attrs: Vec<Attribute>,
+        range: Range,
+    },
+    /// single (`$`) or ranged (`$$`) definition
+    Definition {
+        attrs: Vec<Attribute>,
+        title: Vec<NorgInline<S>>,
+        contents: Vec<Self>,
+        range: Range,
+    },
+    /// single (`^`) or ranged (`^^`) footnote
+    Footnote {
+        attrs: Vec<Attribute>,
+        title: Vec<NorgInline<S>>,
+        /// slug of the title, unique among footnotes. `{^ title}` references match the first
+        /// footnote with that title
+        id: String,
+        contents: Vec<Self>,
+        range: Range,
+    },
+    Table {
+        attrs: Vec<Attribute>,
+        rows: Vec<TableRow<S>>,
+        range: Range,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "Heading"),
+    ts(concrete(S = String))
+)]
+pub struct Heading<S = String> {
+    /// attributes in `* (this) heading` form, applied to the heading only
+    pub attrs: Vec<Attribute>,
+    pub todo: Option<Todo>,
+    /// slug of the heading text, unique within the document
+    pub id: String,
+    pub inlines: Vec<NorgInline<S>>,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "ListItem"),
+    ts(concrete(S = String))
+)]
+pub struct ListItem<S = String> {
+    pub attrs: Vec<Attribute>,
+    pub todo: Option<Todo>,
+    /// carryover tags applied to this item, innermost first
+    pub tags: Vec<Tag<S>>,
+    pub contents: Vec<NorgBlock<S>>,
+    pub range: Range,
+}
+
+/// TODO status from detached modifier extensions, e.g. `- (x) done task`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub struct Todo {
+    pub status: TodoStatus,
+    /// `(< date)`, as written
+    pub due: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub enum TodoStatus {
+    /// `( )`
+    Undone,
+    /// `(x)`
+    Done,
+    /// `(?)`
+    Uncertain,
+    /// `(!)`
+    Urgent,
+    /// `(+)`, with recurrence as written in `(+ Friday)` form
+    Recurring(Option<String>),
+    /// `(-)`
+    Pending,
+    /// `(=)`
+    OnHold,
+    /// `(_)`
+    Canceled,
+}
+
+impl Todo {
+    /// Read TODO status from detached modifier extensions. Item with only a due date is
+    /// undone. Blank attribute is the undone marker only when it stands alone, as in `( )`, so
+    /// trailing `;` in `(key; )` doesn't make a TODO.
+    pub fn from_attrs(attrs: &[Attribute]) -> Option<Self> {
+        let mut status = None;
+        let mut due = None;
+        if let [Attribute::Blank] = attrs {
+            status = Some(TodoStatus::Undone);
+        }
+        for attr in attrs {
+            match attr {
+                Attribute::Blank => {}
+                Attribute::Key(key) => match key.as_str() {
+                    "x" => status = Some(TodoStatus::Done),
+                    "?" => status = Some(TodoStatus::Uncertain),
+                    "!" => status = Some(TodoStatus::Urgent),
+                    "+" => status = Some(TodoStatus::Recurring(None)),
+                    "-" => status = Some(TodoStatus::Pending),
+                    "=" => status = Some(TodoStatus::OnHold),
+                    "_" => status = Some(TodoStatus::Canceled),
+                    _ => {}
+                },
+                Attribute::KeyValue(key, value) => match key.as_str() {
+                    "+" => status = Some(TodoStatus::Recurring(Some(value.clone()))),
+                    "<" => due = Some(value.clone()),
+                    _ => {}
+                },
+            }
+        }
+        match (status, due) {
+            (None, None) => None,
+            (status, due) => Some(Self {
+                status: status.unwrap_or(TodoStatus::Undone),
+                due,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<Janet> for Todo {
+    fn into(self) -> Janet {
+        use TodoStatus::*;
+        let (status, recurrence): (&[u8], _) = match self.status {
+            Undone => (b"undone", None),
+            Done => (b"done", None),
+            Uncertain => (b"uncertain", None),
+            Urgent => (b"urgent", None),
+            Recurring(recurrence) => (b"recurring", recurrence),
+            Pending => (b"pending", None),
+            OnHold => (b"on-hold", None),
+            Canceled => (b"canceled", None),
+        };
+        JanetStruct::builder(3)
+            .put(JanetKeyword::new(b"status"), JanetKeyword::new(status))
+            .put(
+                JanetKeyword::new(b"recurrence"),
+                match recurrence {
+                    Some(recurrence) => Janet::string(recurrence.into()),
+                    None => Janet::nil(),
+                },
+            )
+            .put(
+                JanetKeyword::new(b"due"),
+                match self.due {
+                    Some(due) => Janet::string(due.into()),
+                    None => Janet::nil(),
+                },
+            )
+            .finalize()
+            .into()
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct ListItem {
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "TableRow"),
+    ts(concrete(S = String))
+)]
+pub struct TableRow<S = String> {
+    /// row is part of the table header
+    pub header: bool,
+    pub cells: Vec<TableCell<S>>,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "TableCell"),
+    ts(concrete(S = String))
+)]
+pub struct TableCell<S = String> {
     pub attrs: Vec<Attribute>,
-    pub contents: Vec<NorgBlock>,
+    pub inlines: Vec<NorgInline<S>>,
+    pub range: Range,
+}
+
+/// Carryover tag (`#name params`) applied to a list item. Visitors also get the tag of a
+/// [`NorgBlock::CarryoverTag`] as one, with the range of the whole block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "Tag"),
+    ts(concrete(S = String))
+)]
+pub struct Tag<S = String> {
+    pub name: S,
+    pub params: Vec<String>,
+    pub range: Range,
+}
+
+impl<S> NorgBlock<S> {
+    /// source span of this block. Blocks created from janet have default (empty) span.
+    pub fn range(&self) -> Range {
+        use NorgBlock::*;
+        match self {
+            Section { range, .. }
+            | Paragraph { range, .. }
+            | UnorderedList { range, .. }
+            | OrderedList { range, .. }
+            | Quote { range, .. }
+            | InfirmTag { range, .. }
+            | CarryoverTag { range, .. }
+            | RangedTag { range, .. }
+            | HorizontalLine { range, .. }
+            | Definition { range, .. }
+            | Footnote { range, .. }
+            | Table { range, .. } => *range,
+            #[cfg(feature = "janet")]
+            Embed { range, .. } => *range,
+        }
+    }
+}
+
+/// Walks the AST by reference, in the style of `syn::visit`. Each method defaults to the
+/// `visit_*` function of the same name, which visits every child. Override a method to look
+/// at nodes, and call the function from it to keep walking.
+pub trait Visit {
+    fn visit_block(&mut self, block: &NorgBlock) {
+        visit_block(self, block)
+    }
+    fn visit_heading(&mut self, heading: &Heading) {
+        visit_heading(self, heading)
+    }
+    fn visit_list_item(&mut self, item: &ListItem) {
+        visit_list_item(self, item)
+    }
+    fn visit_tag(&mut self, _tag: &Tag) {}
+    fn visit_table_row(&mut self, row: &TableRow) {
+        visit_table_row(self, row)
+    }
+    fn visit_table_cell(&mut self, cell: &TableCell) {
+        visit_table_cell(self, cell)
+    }
+    fn visit_inline(&mut self, inline: &NorgInline) {
+        crate::inline::visit_inline(self, inline)
+    }
+}
+
+/// Same as [`Visit`], with mutable references.
+pub trait VisitMut {
+    fn visit_block_mut(&mut self, block: &mut NorgBlock) {
+        visit_block_mut(self, block)
+    }
+    fn visit_heading_mut(&mut self, heading: &mut Heading) {
+        visit_heading_mut(self, heading)
+    }
+    fn visit_list_item_mut(&mut self, item: &mut ListItem) {
+        visit_list_item_mut(self, item)
+    }
+    fn visit_tag_mut(&mut self, _tag: &mut Tag) {}
+    fn visit_table_row_mut(&mut self, row: &mut TableRow) {
+        visit_table_row_mut(self, row)
+    }
+    fn visit_table_cell_mut(&mut self, cell: &mut TableCell) {
+        visit_table_cell_mut(self, cell)
+    }
+    fn visit_inline_mut(&mut self, inline: &mut NorgInline) {
+        crate::inline::visit_inline_mut(self, inline)
+    }
+}
+
+/// Rebuilds the AST from owned nodes, in the style of `syn::fold`. Defaults to the `fold_*`
+/// function of the same name, which folds every child.
+pub trait Fold {
+    fn fold_block(&mut self, block: NorgBlock) -> NorgBlock {
+        fold_block(self, block)
+    }
+    fn fold_heading(&mut self, heading: Heading) -> Heading {
+        fold_heading(self, heading)
+    }
+    fn fold_list_item(&mut self, item: ListItem) -> ListItem {
+        fold_list_item(self, item)
+    }
+    fn fold_tag(&mut self, tag: Tag) -> Tag {
+        tag
+    }
+    fn fold_table_row(&mut self, row: TableRow) -> TableRow {
+        fold_table_row(self, row)
+    }
+    fn fold_table_cell(&mut self, cell: TableCell) -> TableCell {
+        fold_table_cell(self, cell)
+    }
+    fn fold_inline(&mut self, inline: NorgInline) -> NorgInline {
+        crate::inline::fold_inline(self, inline)
+    }
+}
+
+pub fn visit_block<V: Visit + ?Sized>(v: &mut V, block: &NorgBlock) {
+    use NorgBlock::*;
+    match block {
+        Section {
+            heading, contents, ..
+        } => {
+            if let Some(heading) = heading {
+                v.visit_heading(heading);
+            }
+            for block in contents {
+                v.visit_block(block);
+            }
+        }
+        Paragraph { inlines, .. } => {
+            for inline in inlines {
+                v.visit_inline(inline);
+            }
+        }
+        UnorderedList { items, .. } | OrderedList { items, .. } | Quote { items, .. } => {
+            for item in items {
+                v.visit_list_item(item);
+            }
+        }
+        CarryoverTag {
+            params,
+            name,
+            target,
+            range,
+        } => {
+            v.visit_tag(&Tag {
+                name: name.clone(),
+                params: params.clone(),
+                range: *range,
+            });
+            v.visit_block(target);
+        }
+        Definition {
+            title, contents, ..
+        }
+        | Footnote {
+            title, contents, ..
+        } => {
+            for inline in title {
+                v.visit_inline(inline);
+            }
+            for block in contents {
+                v.visit_block(block);
+            }
+        }
+        Table { rows, .. } => {
+            for row in rows {
+                v.visit_table_row(row);
+            }
+        }
+        InfirmTag { .. } | RangedTag { .. } | HorizontalLine { .. } => {}
+        #[cfg(feature = "janet")]
+        Embed { .. } => {}
+    }
+}
+
+pub fn visit_heading<V: Visit + ?Sized>(v: &mut V, heading: &Heading) {
+    for inline in &heading.inlines {
+        v.visit_inline(inline);
+    }
+}
+
+pub fn visit_list_item<V: Visit + ?Sized>(v: &mut V, item: &ListItem) {
+    for tag in &item.tags {
+        v.visit_tag(tag);
+    }
+    for block in &item.contents {
+        v.visit_block(block);
+    }
+}
+
+pub fn visit_table_row<V: Visit + ?Sized>(v: &mut V, row: &TableRow) {
+    for cell in &row.cells {
+        v.visit_table_cell(cell);
+    }
+}
+
+pub fn visit_table_cell<V: Visit + ?Sized>(v: &mut V, cell: &TableCell) {
+    for inline in &cell.inlines {
+        v.visit_inline(inline);
+    }
+}
+
+pub fn visit_block_mut<V: VisitMut + ?Sized>(v: &mut V, block: &mut NorgBlock) {
+    use NorgBlock::*;
+    match block {
+        Section {
+            heading, contents, ..
+        } => {
+            if let Some(heading) = heading {
+                v.visit_heading_mut(heading);
+            }
+            for block in contents {
+                v.visit_block_mut(block);
+            }
+        }
+        Paragraph { inlines, .. } => {
+            for inline in inlines {
+                v.visit_inline_mut(inline);
+            }
+        }
+        UnorderedList { items, .. } | OrderedList { items, .. } | Quote { items, .. } => {
+            for item in items {
+                v.visit_list_item_mut(item);
+            }
+        }
+        CarryoverTag {
+            params,
+            name,
+            target,
+            range,
+        } => {
+            let mut tag = Tag {
+                name: std::mem::take(name),
+                params: std::mem::take(params),
+                range: *range,
+            };
+            v.visit_tag_mut(&mut tag);
+            (*name, *params) = (tag.name, tag.params);
+            v.visit_block_mut(target);
+        }
+        Definition {
+            title, contents, ..
+        }
+        | Footnote {
+            title, contents, ..
+        } => {
+            for inline in title {
+                v.visit_inline_mut(inline);
+            }
+            for block in contents {
+                v.visit_block_mut(block);
+            }
+        }
+        Table { rows, .. } => {
+            for row in rows {
+                v.visit_table_row_mut(row);
+            }
+        }
+        InfirmTag { .. } | RangedTag { .. } | HorizontalLine { .. } => {}
+        #[cfg(feature = "janet")]
+        Embed { .. } => {}
+    }
+}
+
+pub fn visit_heading_mut<V: VisitMut + ?Sized>(v: &mut V, heading: &mut Heading) {
+    for inline in &mut heading.inlines {
+        v.visit_inline_mut(inline);
+    }
+}
+
+pub fn visit_list_item_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut ListItem) {
+    for tag in &mut item.tags {
+        v.visit_tag_mut(tag);
+    }
+    for block in &mut item.contents {
+        v.visit_block_mut(block);
+    }
+}
+
+pub fn visit_table_row_mut<V: VisitMut + ?Sized>(v: &mut V, row: &mut TableRow) {
+    for cell in &mut row.cells {
+        v.visit_table_cell_mut(cell);
+    }
 }
 
+pub fn visit_table_cell_mut<V: VisitMut + ?Sized>(v: &mut V, cell: &mut TableCell) {
+    for inline in &mut cell.inlines {
+        v.visit_inline_mut(inline);
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(f: &mut F, block: NorgBlock) -> NorgBlock {
+    use NorgBlock::*;
+    match block {
+        Section {
+            attrs,
+            level,
+            heading,
+            contents,
+            range,
+        } => Section {
+            attrs,
+            level,
+            heading: heading.map(|heading| f.fold_heading(heading)),
+            contents: fold_blocks(f, contents),
+            range,
+        },
+        Paragraph {
+            attrs,
+            inlines,
+            range,
+        } => Paragraph {
+            attrs,
+            inlines: fold_inlines(f, inlines),
+            range,
+        },
+        UnorderedList {
+            attrs,
+            level,
+            items,
+            range,
+        } => UnorderedList {
+            attrs,
+            level,
+            items: fold_items(f, items),
+            range,
+        },
+        OrderedList {
+            attrs,
+            level,
+            items,
+            range,
+        } => OrderedList {
+            attrs,
+            level,
+            items: fold_items(f, items),
+            range,
+        },
+        Quote {
+            attrs,
+            level,
+            items,
+            range,
+        } => Quote {
+            attrs,
+            level,
+            items: fold_items(f, items),
+            range,
+        },
+        CarryoverTag {
+            params,
+            name,
+            target,
+            range,
+        } => {
+            let tag = f.fold_tag(Tag {
+                name,
+                params,
+                range,
+            });
+            CarryoverTag {
+                params: tag.params,
+                name: tag.name,
+                target: Box::new(f.fold_block(*target)),
+                range,
+            }
+        }
+        Definition {
+            attrs,
+            title,
+            contents,
+            range,
+        } => Definition {
+            attrs,
+            title: fold_inlines(f, title),
+            contents: fold_blocks(f, contents),
+            range,
+        },
+        Footnote {
+            attrs,
+            title,
+            id,
+            contents,
+            range,
+        } => Footnote {
+            attrs,
+            title: fold_inlines(f, title),
+            id,
+            contents: fold_blocks(f, contents),
+            range,
+        },
+        Table { attrs, rows, range } => Table {
+            attrs,
+            rows: rows.into_iter().map(|row| f.fold_table_row(row)).collect(),
+            range,
+        },
+        block @ (InfirmTag { .. } | RangedTag { .. } | HorizontalLine { .. }) => block,
+        #[cfg(feature = "janet")]
+        block @ Embed { .. } => block,
+    }
+}
+
+pub fn fold_heading<F: Fold + ?Sized>(f: &mut F, heading: Heading) -> Heading {
+    Heading {
+        inlines: fold_inlines(f, heading.inlines),
+        ..heading
+    }
+}
+
+pub fn fold_list_item<F: Fold + ?Sized>(f: &mut F, item: ListItem) -> ListItem {
+    ListItem {
+        tags: item.tags.into_iter().map(|tag| f.fold_tag(tag)).collect(),
+        contents: fold_blocks(f, item.contents),
+        ..item
+    }
+}
+
+pub fn fold_table_row<F: Fold + ?Sized>(f: &mut F, row: TableRow) -> TableRow {
+    TableRow {
+        cells: row
+            .cells
+            .into_iter()
+            .map(|cell| f.fold_table_cell(cell))
+            .collect(),
+        ..row
+    }
+}
+
+pub fn fold_table_cell<F: Fold + ?Sized>(f: &mut F, cell: TableCell) -> TableCell {
+    TableCell {
+        inlines: fold_inlines(f, cell.inlines),
+        ..cell
+    }
+}
+
+fn fold_blocks<F: Fold + ?Sized>(f: &mut F, blocks: Vec<NorgBlock>) -> Vec<NorgBlock> {
+    blocks.into_iter().map(|block| f.fold_block(block)).collect()
+}
+
+fn fold_items<F: Fold + ?Sized>(f: &mut F, items: Vec<ListItem>) -> Vec<ListItem> {
+    items.into_iter().map(|item| f.fold_list_item(item)).collect()
+}
+
+fn fold_inlines<F: Fold + ?Sized>(f: &mut F, inlines: Vec<NorgInline>) -> Vec<NorgInline> {
+    inlines
+        .into_iter()
+        .map(|inline| f.fold_inline(inline))
+        .collect()
+}
+
+#[cfg(feature = "janet")]
 impl TryFrom<Janet> for NorgBlock {
     // TODO: change JanetConversionError::Other to more verbose one
     type Error = JanetConversionError;
@@ -82,6 +735,7 @@ impl TryFrom<Janet> for NorgBlock {
             .get_owned(JanetKeyword::new(b"kind"))
             .ok_or(JanetConversionError::Other)?
             .try_into()?;
+        let range = Range::from_janet_node(&value)?;
         let node = match kind.as_bytes() {
             b"embed" => NorgBlock::Embed {
                 attrs: vec![],
@@ -89,6 +743,7 @@ impl TryFrom<Janet> for NorgBlock {
                     .get_owned(JanetKeyword::new(b"export"))
                     .ok_or(JanetConversionError::Other)?
                     .try_into()?,
+                range,
             },
             b"section" => {
                 let level = value
@@ -96,19 +751,9 @@ impl TryFrom<Janet> for NorgBlock {
                     .ok_or(JanetConversionError::Other)?
                     .try_unwrap::<u32>()? as u16;
                 let heading = value
-                    .get(JanetKeyword::new(b"heading"))
-                    .map(|inlines| match inlines.unwrap() {
-                        TaggedJanet::Tuple(tuple) => {
-                            tuple.iter().map(|&inline| inline.try_into()).collect()
-                        }
-                        TaggedJanet::Array(array) => {
-                            array.iter().map(|&inline| inline.try_into()).collect()
-                        }
-                        got => Err(JanetConversionError::multi_wrong_kind(
-                            vec![JanetType::Array, JanetType::Tuple],
-                            got.kind(),
-                        )),
-                    })
+                    .get_owned(JanetKeyword::new(b"heading"))
+                    .filter(|heading| !heading.is_nil())
+                    .map(Heading::try_from)
                     .transpose()?;
                 let contents: Vec<NorgBlock> = match value
                     .get(JanetKeyword::new(b"contents"))
@@ -126,6 +771,7 @@ impl TryFrom<Janet> for NorgBlock {
                     level,
                     heading,
                     contents,
+                    range,
                 }
             }
             b"paragraph" => NorgBlock::Paragraph {
@@ -151,13 +797,35 @@ impl TryFrom<Janet> for NorgBlock {
                         }
                     }
                 },
+                range,
             },
             b"infirm-tag" => {
                 let name = value
                     .get_owned(JanetKeyword::new(b"name"))
                     .ok_or(JanetConversionError::Other)?;
                 let name = JanetString::try_from(name)?.to_string();
-                NorgBlock::InfirmTag { params: None, name }
+                NorgBlock::InfirmTag {
+                    attrs: vec![],
+                    params: params_from_janet(&value)?,
+                    name,
+                    range,
+                }
+            }
+            b"carryover-tag" => {
+                let name = value
+                    .get_owned(JanetKeyword::new(b"name"))
+                    .ok_or(JanetConversionError::Other)?;
+                NorgBlock::CarryoverTag {
+                    params: params_from_janet(&value)?,
+                    name: JanetString::try_from(name)?.to_string(),
+                    target: Box::new(
+                        value
+                            .get_owned(JanetKeyword::new(b"target"))
+                            .ok_or(JanetConversionError::Other)?
+                            .try_into()?,
+                    ),
+                    range,
+                }
             }
             b"unordered-list" | b"ordered-list" | b"quote" => {
                 let attrs = vec![];
@@ -183,47 +851,314 @@ impl TryFrom<Janet> for NorgBlock {
                     }
                 };
                 match kind.as_bytes() {
-                    b"unorderd-list" => Self::UnorderedList {
+                    b"unordered-list" => Self::UnorderedList {
                         attrs,
                         level,
                         items,
+                        range,
                     },
-                    b"orderd-list" => Self::OrderedList {
+                    b"ordered-list" => Self::OrderedList {
                         attrs,
                         level,
                         items,
+                        range,
                     },
-                    b"quote-list" => Self::Quote {
+                    b"quote" => Self::Quote {
                         attrs,
                         level,
                         items,
+                        range,
                     },
                     _ => unreachable!(),
                 }
             }
-            _ => unimplemented!("implement for kind: {kind}"),
+            b"ranged-tag" => {
+                let name = value
+                    .get_owned(JanetKeyword::new(b"name"))
+                    .ok_or(JanetConversionError::Other)?;
+                NorgBlock::RangedTag {
+                    attrs: vec![],
+                    params: params_from_janet(&value)?,
+                    name: JanetString::try_from(name)?.to_string(),
+                    content: strings_from_janet(&value, b"content")?,
+                    range,
+                }
+            }
+            b"horizontal-line" => NorgBlock::HorizontalLine {
+                attrs: vec![],
+                range,
+            },
+            b"definition" => NorgBlock::Definition {
+                attrs: vec![],
+                title: nodes_from_janet(&value, b"title")?,
+                contents: nodes_from_janet(&value, b"contents")?,
+                range,
+            },
+            b"footnote" => {
+                let title: Vec<NorgInline> = nodes_from_janet(&value, b"title")?;
+                // footnotes made in janet may not have an id
+                let id = match value.get_owned(JanetKeyword::new(b"id")) {
+                    Some(id) => JanetString::try_from(id)?.to_string(),
+                    None => slugify(&markup_text(&title)),
+                };
+                NorgBlock::Footnote {
+                    attrs: vec![],
+                    title,
+                    id,
+                    contents: nodes_from_janet(&value, b"contents")?,
+                    range,
+                }
+            }
+            b"table" => NorgBlock::Table {
+                attrs: vec![],
+                rows: nodes_from_janet(&value, b"rows")?,
+                range,
+            },
+            _ => return Err(JanetConversionError::Other),
         };
         Ok(node)
     }
 }
 
+/// Janet side has no separate place for item tags, so tagged items are wrapped in
+/// `:carryover-tag` nodes like blocks are.
+#[cfg(feature = "janet")]
 impl Into<Janet> for ListItem {
     fn into(self) -> Janet {
-        JanetStruct::builder(3)
+        let item = JanetStruct::builder(5)
             .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"list-item"))
             .put(
                 JanetKeyword::new(b"attrs"),
                 Janet::tuple(self.attrs.into_iter().collect()),
             )
+            .put(
+                JanetKeyword::new(b"todo"),
+                match self.todo {
+                    Some(todo) => todo.into(),
+                    None => Janet::nil(),
+                },
+            )
             .put(
                 JanetKeyword::new(b"contents"),
                 Janet::tuple(self.contents.into_iter().collect()),
             )
+            .put(JanetKeyword::new(b"range"), self.range)
+            .finalize()
+            .into();
+        self.tags.into_iter().fold(item, |target: Janet, tag| {
+            JanetStruct::builder(5)
+                .put(
+                    JanetKeyword::new(b"kind"),
+                    JanetKeyword::new(b"carryover-tag"),
+                )
+                .put(
+                    JanetKeyword::new(b"params"),
+                    Janet::tuple(tag.params.iter().map(|x| x.as_str()).collect()),
+                )
+                .put(JanetKeyword::new(b"name"), tag.name.as_str())
+                .put(JanetKeyword::new(b"target"), target)
+                .put(JanetKeyword::new(b"range"), tag.range)
+                .finalize()
+                .into()
+        })
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<Janet> for TableRow {
+    fn into(self) -> Janet {
+        JanetStruct::builder(4)
+            .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"table-row"))
+            .put(JanetKeyword::new(b"header"), self.header)
+            .put(
+                JanetKeyword::new(b"cells"),
+                Janet::tuple(self.cells.into_iter().collect()),
+            )
+            .put(JanetKeyword::new(b"range"), self.range)
+            .finalize()
+            .into()
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<Janet> for TableCell {
+    fn into(self) -> Janet {
+        JanetStruct::builder(4)
+            .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"table-cell"))
+            .put(
+                JanetKeyword::new(b"attrs"),
+                Janet::tuple(self.attrs.into_iter().collect()),
+            )
+            .put(
+                JanetKeyword::new(b"inlines"),
+                Janet::tuple(self.inlines.into_iter().collect()),
+            )
+            .put(JanetKeyword::new(b"range"), self.range)
             .finalize()
             .into()
     }
 }
 
+#[cfg(feature = "janet")]
+impl TryFrom<Janet> for TableRow {
+    type Error = JanetConversionError;
+
+    fn try_from(value: Janet) -> Result<Self, Self::Error> {
+        let value: JanetStruct = value.try_into()?;
+        let header = value
+            .get_owned(JanetKeyword::new(b"header"))
+            .is_some_and(|header| header.is_truthy());
+        Ok(Self {
+            header,
+            cells: nodes_from_janet(&value, b"cells")?,
+            range: Range::from_janet_node(&value)?,
+        })
+    }
+}
+
+#[cfg(feature = "janet")]
+impl TryFrom<Janet> for TableCell {
+    type Error = JanetConversionError;
+
+    fn try_from(value: Janet) -> Result<Self, Self::Error> {
+        let value: JanetStruct = value.try_into()?;
+        Ok(Self {
+            // TODO: parse attrs
+            attrs: vec![],
+            inlines: nodes_from_janet(&value, b"inlines")?,
+            range: Range::from_janet_node(&value)?,
+        })
+    }
+}
+
+/// read `:params` of tag node
+#[cfg(feature = "janet")]
+fn params_from_janet(value: &JanetStruct) -> Result<Vec<String>, JanetConversionError> {
+    strings_from_janet(value, b"params")
+}
+
+/// read tuple or array of nodes at `key`
+#[cfg(feature = "janet")]
+fn nodes_from_janet<T>(value: &JanetStruct, key: &[u8]) -> Result<Vec<T>, JanetConversionError>
+where
+    T: TryFrom<Janet, Error = JanetConversionError>,
+{
+    match value
+        .get(JanetKeyword::new(key))
+        .ok_or(JanetConversionError::Other)?
+        .unwrap()
+    {
+        TaggedJanet::Tuple(tuple) => tuple.iter().map(|&node| node.try_into()).collect(),
+        TaggedJanet::Array(array) => array.iter().map(|&node| node.try_into()).collect(),
+        got => Err(JanetConversionError::multi_wrong_kind(
+            vec![JanetType::Array, JanetType::Tuple],
+            got.kind(),
+        )),
+    }
+}
+
+/// read tuple or array of strings at `key`, empty when missing
+#[cfg(feature = "janet")]
+fn strings_from_janet(
+    value: &JanetStruct,
+    key: &[u8],
+) -> Result<Vec<String>, JanetConversionError> {
+    match value.get_owned(JanetKeyword::new(key)) {
+        None => Ok(vec![]),
+        Some(params) => match params.unwrap() {
+            TaggedJanet::Tuple(tuple) => tuple
+                .iter()
+                .map(|&param| Ok(JanetString::try_from(param)?.to_string()))
+                .collect(),
+            TaggedJanet::Array(array) => array
+                .iter()
+                .map(|&param| Ok(JanetString::try_from(param)?.to_string()))
+                .collect(),
+            got => Err(JanetConversionError::multi_wrong_kind(
+                vec![JanetType::Array, JanetType::Tuple],
+                got.kind(),
+            )),
+        },
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<Janet> for Heading {
+    fn into(self) -> Janet {
+        JanetStruct::builder(6)
+            .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"heading"))
+            .put(JanetKeyword::new(b"id"), self.id.as_str())
+            .put(
+                JanetKeyword::new(b"attrs"),
+                Janet::tuple(self.attrs.into_iter().collect()),
+            )
+            .put(
+                JanetKeyword::new(b"todo"),
+                match self.todo {
+                    Some(todo) => todo.into(),
+                    None => Janet::nil(),
+                },
+            )
+            .put(
+                JanetKeyword::new(b"inlines"),
+                Janet::tuple(self.inlines.into_iter().collect()),
+            )
+            .put(JanetKeyword::new(b"range"), self.range)
+            .finalize()
+            .into()
+    }
+}
+
+#[cfg(feature = "janet")]
+impl TryFrom<Janet> for Heading {
+    type Error = JanetConversionError;
+
+    fn try_from(value: Janet) -> Result<Self, Self::Error> {
+        let value: JanetStruct = value.try_into()?;
+        let kind: JanetKeyword = value
+            .get_owned(JanetKeyword::new(b"kind"))
+            .ok_or(JanetConversionError::Other)?
+            .try_into()?;
+        if kind != JanetKeyword::new(b"heading") {
+            return Err(JanetConversionError::Other);
+        }
+        let inlines: Vec<NorgInline> = match value
+            .get(JanetKeyword::new(b"inlines"))
+            .ok_or(JanetConversionError::Other)?
+            .unwrap()
+        {
+            TaggedJanet::Tuple(tuple) => tuple
+                .iter()
+                .map(|&inline| inline.try_into())
+                .collect::<Result<_, _>>()?,
+            TaggedJanet::Array(array) => array
+                .iter()
+                .map(|&inline| inline.try_into())
+                .collect::<Result<_, _>>()?,
+            got => {
+                return Err(JanetConversionError::multi_wrong_kind(
+                    vec![JanetType::Array, JanetType::Tuple],
+                    got.kind(),
+                ));
+            }
+        };
+        // headings made in janet may not have an id
+        let id = match value.get_owned(JanetKeyword::new(b"id")) {
+            Some(id) => JanetString::try_from(id)?.to_string(),
+            None => slugify(&markup_text(&inlines)),
+        };
+        Ok(Self {
+            // TODO: parse attrs
+            attrs: vec![],
+            todo: None,
+            id,
+            inlines,
+            range: Range::from_janet_node(&value)?,
+        })
+    }
+}
+
+#[cfg(feature = "janet")]
 impl TryFrom<Janet> for ListItem {
     type Error = JanetConversionError;
 
@@ -233,6 +1168,22 @@ impl TryFrom<Janet> for ListItem {
             .get_owned(JanetKeyword::new(b"kind"))
             .ok_or(JanetConversionError::Other)?
             .try_into()?;
+        if kind == JanetKeyword::new(b"carryover-tag") {
+            let name = value
+                .get_owned(JanetKeyword::new(b"name"))
+                .ok_or(JanetConversionError::Other)?;
+            let mut item = ListItem::try_from(
+                value
+                    .get_owned(JanetKeyword::new(b"target"))
+                    .ok_or(JanetConversionError::Other)?,
+            )?;
+            item.tags.push(Tag {
+                name: JanetString::try_from(name)?.to_string(),
+                params: params_from_janet(&value)?,
+                range: Range::from_janet_node(&value)?,
+            });
+            return Ok(item);
+        }
         if kind != JanetKeyword::new(b"list-item") {
             return Err(JanetConversionError::Other);
         }
@@ -259,11 +1210,15 @@ impl TryFrom<Janet> for ListItem {
         Ok(Self {
             // TODO: parse attrs
             attrs: vec![],
+            todo: None,
+            tags: vec![],
             contents,
+            range: Range::from_janet_node(&value)?,
         })
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<Janet> for NorgBlock {
     fn into(self) -> Janet {
         use NorgBlock::*;
@@ -273,7 +1228,8 @@ impl Into<Janet> for NorgBlock {
                 level,
                 heading,
                 contents,
-            } => JanetStruct::builder(5)
+                range,
+            } => JanetStruct::builder(6)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"section"))
                 .put(
                     JanetKeyword::new(b"attrs"),
@@ -283,7 +1239,7 @@ impl Into<Janet> for NorgBlock {
                 .put(
                     JanetKeyword::new(b"heading"),
                     match heading {
-                        Some(heading) => Janet::tuple(heading.into_iter().collect()),
+                        Some(heading) => heading.into(),
                         None => Janet::nil(),
                     },
                 )
@@ -291,9 +1247,14 @@ impl Into<Janet> for NorgBlock {
                     JanetKeyword::new(b"contents"),
                     Janet::tuple(contents.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
-            Paragraph { attrs, inlines } => JanetStruct::builder(3)
+            Paragraph {
+                attrs,
+                inlines,
+                range,
+            } => JanetStruct::builder(4)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"paragraph"))
                 .put(
                     JanetKeyword::new(b"attrs"),
@@ -303,54 +1264,72 @@ impl Into<Janet> for NorgBlock {
                     JanetKeyword::new(b"inlines"),
                     Janet::tuple(inlines.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
-            InfirmTag { params, name } => JanetStruct::builder(3)
+            InfirmTag {
+                attrs,
+                params,
+                name,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"infirm-tag"))
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
                 .put(
                     JanetKeyword::new(b"params"),
-                    match params {
-                        Some(params) => Janet::string(params.as_bytes().into()),
-                        None => Janet::nil(),
-                    },
+                    Janet::tuple(params.iter().map(|x| x.as_str()).collect()),
                 )
                 .put(JanetKeyword::new(b"name"), name.as_str())
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
             RangedTag {
+                attrs,
                 params,
                 name,
                 content,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(6)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"ranged-tag"))
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
                 .put(
                     JanetKeyword::new(b"params"),
-                    match params {
-                        Some(params) => Janet::string(params.as_bytes().into()),
-                        None => Janet::nil(),
-                    },
+                    Janet::tuple(params.iter().map(|x| x.as_str()).collect()),
                 )
                 .put(JanetKeyword::new(b"name"), name.as_str())
                 .put(
                     JanetKeyword::new(b"content"),
                     Janet::tuple(content.iter().map(|x| x.as_str()).collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
-            Embed { attrs, export } => JanetStruct::builder(3)
+            Embed {
+                attrs,
+                export,
+                range,
+            } => JanetStruct::builder(4)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"embed"))
                 .put(
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
                 .put(JanetKeyword::new(b"export"), export)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
             UnorderedList {
                 attrs,
                 level,
                 items,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(5)
                 .put(
                     JanetKeyword::new(b"kind"),
                     JanetKeyword::new(b"unordered-list"),
@@ -364,13 +1343,15 @@ impl Into<Janet> for NorgBlock {
                     JanetKeyword::new(b"items"),
                     Janet::tuple(items.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
             OrderedList {
                 attrs,
                 level,
                 items,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(5)
                 .put(
                     JanetKeyword::new(b"kind"),
                     JanetKeyword::new(b"ordered-list"),
@@ -384,13 +1365,15 @@ impl Into<Janet> for NorgBlock {
                     JanetKeyword::new(b"items"),
                     Janet::tuple(items.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
             Quote {
                 attrs,
                 level,
                 items,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"quote"))
                 .put(
                     JanetKeyword::new(b"attrs"),
@@ -401,36 +1384,218 @@ impl Into<Janet> for NorgBlock {
                     JanetKeyword::new(b"items"),
                     Janet::tuple(items.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
             CarryoverTag {
                 params,
                 name,
                 target,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(5)
                 .put(
                     JanetKeyword::new(b"kind"),
                     JanetKeyword::new(b"carryover-tag"),
                 )
                 .put(
                     JanetKeyword::new(b"params"),
-                    match params {
-                        Some(params) => Janet::string(params.as_bytes().into()),
-                        None => Janet::nil(),
-                    },
+                    Janet::tuple(params.iter().map(|x| x.as_str()).collect()),
                 )
                 .put(JanetKeyword::new(b"name"), name.as_str())
-                .put(JanetKeyword::new(b"block"), *target)
+                .put(JanetKeyword::new(b"target"), *target)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
-            HorizontalLine { attrs } => JanetStruct::builder(2)
+            HorizontalLine { attrs, range } => JanetStruct::builder(3)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"horizontal-line"))
                 .put(
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize()
+                .into(),
+            Definition {
+                attrs,
+                title,
+                contents,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"definition"))
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"title"),
+                    Janet::tuple(title.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"contents"),
+                    Janet::tuple(contents.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize()
+                .into(),
+            Footnote {
+                attrs,
+                title,
+                id,
+                contents,
+                range,
+            } => JanetStruct::builder(6)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"footnote"))
+                .put(JanetKeyword::new(b"id"), id.as_str())
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"title"),
+                    Janet::tuple(title.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"contents"),
+                    Janet::tuple(contents.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize()
+                .into(),
+            Table { attrs, rows, range } => JanetStruct::builder(4)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"table"))
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"rows"),
+                    Janet::tuple(rows.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize()
                 .into(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_todo_from_attrs() {
+        assert_eq!(Todo::from_attrs(&[Attribute::Key("html.class".to_string())]), None);
+        assert_eq!(
+            Todo::from_attrs(&[Attribute::Key("a".to_string()), Attribute::Blank]),
+            None
+        );
+        assert_eq!(
+            Todo::from_attrs(&[Attribute::Blank]),
+            Some(Todo {
+                status: TodoStatus::Undone,
+                due: None,
+            })
+        );
+        assert_eq!(
+            Todo::from_attrs(&[Attribute::Key("x".to_string())]),
+            Some(Todo {
+                status: TodoStatus::Done,
+                due: None,
+            })
+        );
+        assert_eq!(
+            Todo::from_attrs(&[
+                Attribute::KeyValue("+".to_string(), "Friday".to_string()),
+                Attribute::KeyValue("<".to_string(), "2024-05-01".to_string()),
+            ]),
+            Some(Todo {
+                status: TodoStatus::Recurring(Some("Friday".to_string())),
+                due: Some("2024-05-01".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_visit_and_fold() {
+        struct WordCount(usize);
+        impl Visit for WordCount {
+            fn visit_inline(&mut self, inline: &NorgInline) {
+                if let NorgInline::Text { .. } = inline {
+                    self.0 += 1;
+                }
+                crate::inline::visit_inline(self, inline);
+            }
+        }
+        struct ShiftHeadings;
+        impl VisitMut for ShiftHeadings {
+            fn visit_block_mut(&mut self, block: &mut NorgBlock) {
+                if let NorgBlock::Section { level, .. } = block {
+                    *level += 1;
+                }
+                visit_block_mut(self, block);
+            }
+        }
+        struct DropMarkup;
+        impl Fold for DropMarkup {
+            fn fold_inline(&mut self, inline: NorgInline) -> NorgInline {
+                match crate::inline::fold_inline(self, inline) {
+                    NorgInline::Bold { mut markup, .. } if markup.len() == 1 => markup.remove(0),
+                    inline => inline,
+                }
+            }
+        }
+
+        let mut blocks = crate::parser::parse(b"* a *b*\n- c d\n").blocks;
+        let mut count = WordCount(0);
+        for block in &blocks {
+            count.visit_block(block);
+        }
+        assert_eq!(count.0, 4);
+        for block in &mut blocks {
+            ShiftHeadings.visit_block_mut(block);
+        }
+        let section = DropMarkup.fold_block(blocks.remove(0));
+        let NorgBlock::Section { level, heading, .. } = section else {
+            panic!("expected section, got {section:?}");
+        };
+        assert_eq!(level, 2);
+        let inlines = heading.unwrap().inlines;
+        assert!(matches!(inlines[2], NorgInline::Text { .. }));
+    }
+
+    #[test]
+    fn test_visit_tags() {
+        struct TagCount(usize);
+        impl Visit for TagCount {
+            fn visit_tag(&mut self, _tag: &Tag) {
+                self.0 += 1;
+            }
+        }
+        struct RenameTags;
+        impl VisitMut for RenameTags {
+            fn visit_tag_mut(&mut self, tag: &mut Tag) {
+                tag.name = tag.name.to_uppercase();
+            }
+        }
+        struct DropParams;
+        impl Fold for DropParams {
+            fn fold_tag(&mut self, mut tag: Tag) -> Tag {
+                tag.params.clear();
+                tag
+            }
+        }
+
+        let mut blocks = crate::parser::parse(b"#a x\nparagraph\n\n#b\n- item\n").blocks;
+        let mut count = TagCount(0);
+        for block in &blocks {
+            count.visit_block(block);
+        }
+        assert_eq!(count.0, 2);
+        RenameTags.visit_block_mut(&mut blocks[0]);
+        let NorgBlock::CarryoverTag { name, params, .. } = DropParams.fold_block(blocks.remove(0))
+        else {
+            panic!("expected carryover tag");
+        };
+        assert_eq!((name.as_str(), params.len()), ("A", 0));
+    }
+}
@@ -0,0 +1,429 @@
+//! AST borrowing its text from the parsed source.
+//!
+//! Same types as [`crate::block`] and [`crate::inline`], but words, punctuation, tag names and
+//! ranged tag lines are `&'src str` slices of the input instead of owned copies. Attributes,
+//! tag parameters, link targets and ids are unescaped or derived while parsing, so they stay
+//! owned. Use [`NorgAST::into_owned`] to get the owned AST used by janet and the exporter.
+
+use crate::{block, inline, parser};
+
+pub type NorgAST<'src> = parser::NorgAST<&'src str>;
+pub type NorgBlock<'src> = block::NorgBlock<&'src str>;
+pub type Heading<'src> = block::Heading<&'src str>;
+pub type ListItem<'src> = block::ListItem<&'src str>;
+pub type TableRow<'src> = block::TableRow<&'src str>;
+pub type TableCell<'src> = block::TableCell<&'src str>;
+pub type Tag<'src> = block::Tag<&'src str>;
+pub type NorgInline<'src> = inline::NorgInline<&'src str>;
+
+impl NorgAST<'_> {
+    pub fn into_owned(self) -> parser::NorgAST {
+        parser::NorgAST {
+            anchors: self.anchors,
+            anchor_issues: self.anchor_issues,
+            blocks: owned_blocks(self.blocks),
+            diagnostics: self.diagnostics,
+        }
+    }
+}
+
+impl NorgBlock<'_> {
+    pub fn into_owned(self) -> block::NorgBlock {
+        use block::NorgBlock as Owned;
+        match self {
+            Self::Section {
+                attrs,
+                level,
+                heading,
+                contents,
+                range,
+            } => Owned::Section {
+                attrs,
+                level,
+                heading: heading.map(Heading::into_owned),
+                contents: owned_blocks(contents),
+                range,
+            },
+            Self::Paragraph {
+                attrs,
+                inlines,
+                range,
+            } => Owned::Paragraph {
+                attrs,
+                inlines: owned_inlines(inlines),
+                range,
+            },
+            Self::UnorderedList {
+                attrs,
+                level,
+                items,
+                range,
+            } => Owned::UnorderedList {
+                attrs,
+                level,
+                items: owned_items(items),
+                range,
+            },
+            Self::OrderedList {
+                attrs,
+                level,
+                items,
+                range,
+            } => Owned::OrderedList {
+                attrs,
+                level,
+                items: owned_items(items),
+                range,
+            },
+            Self::Quote {
+                attrs,
+                level,
+                items,
+                range,
+            } => Owned::Quote {
+                attrs,
+                level,
+                items: owned_items(items),
+                range,
+            },
+            Self::InfirmTag {
+                attrs,
+                params,
+                name,
+                range,
+            } => Owned::InfirmTag {
+                attrs,
+                params,
+                name: name.to_string(),
+                range,
+            },
+            Self::CarryoverTag {
+                params,
+                name,
+                target,
+                range,
+            } => Owned::CarryoverTag {
+                params,
+                name: name.to_string(),
+                target: Box::new(target.into_owned()),
+                range,
+            },
+            Self::RangedTag {
+                attrs,
+                params,
+                name,
+                content,
+                range,
+            } => Owned::RangedTag {
+                attrs,
+                params,
+                name: name.to_string(),
+                content: content.into_iter().map(str::to_string).collect(),
+                range,
+            },
+            #[cfg(feature = "janet")]
+            Self::Embed {
+                attrs,
+                export,
+                range,
+            } => Owned::Embed {
+                attrs,
+                export,
+                range,
+            },
+            Self::HorizontalLine { attrs, range } => Owned::HorizontalLine { attrs, range },
+            Self::Definition {
+                attrs,
+                title,
+                contents,
+                range,
+            } => Owned::Definition {
+                attrs,
+                title: owned_inlines(title),
+                contents: owned_blocks(contents),
+                range,
+            },
+            Self::Footnote {
+                attrs,
+                title,
+                id,
+                contents,
+                range,
+            } => Owned::Footnote {
+                attrs,
+                title: owned_inlines(title),
+                id,
+                contents: owned_blocks(contents),
+                range,
+            },
+            Self::Table { attrs, rows, range } => Owned::Table {
+                attrs,
+                rows: rows.into_iter().map(TableRow::into_owned).collect(),
+                range,
+            },
+        }
+    }
+}
+
+impl Heading<'_> {
+    pub fn into_owned(self) -> block::Heading {
+        block::Heading {
+            attrs: self.attrs,
+            todo: self.todo,
+            id: self.id,
+            inlines: owned_inlines(self.inlines),
+            range: self.range,
+        }
+    }
+}
+
+impl ListItem<'_> {
+    pub fn into_owned(self) -> block::ListItem {
+        block::ListItem {
+            attrs: self.attrs,
+            todo: self.todo,
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            contents: owned_blocks(self.contents),
+            range: self.range,
+        }
+    }
+}
+
+impl TableRow<'_> {
+    pub fn into_owned(self) -> block::TableRow {
+        block::TableRow {
+            header: self.header,
+            cells: self
+                .cells
+                .into_iter()
+                .map(|cell| block::TableCell {
+                    attrs: cell.attrs,
+                    inlines: owned_inlines(cell.inlines),
+                    range: cell.range,
+                })
+                .collect(),
+            range: self.range,
+        }
+    }
+}
+
+impl Tag<'_> {
+    pub fn into_owned(self) -> block::Tag {
+        block::Tag {
+            name: self.name.to_string(),
+            params: self.params,
+            range: self.range,
+        }
+    }
+}
+
+impl NorgInline<'_> {
+    pub fn into_owned(self) -> inline::NorgInline {
+        use inline::NorgInline as Owned;
+        match self {
+            Self::Text { text, range } => Owned::Text {
+                text: text.to_string(),
+                range,
+            },
+            Self::Special { text, range } => Owned::Special {
+                text: text.to_string(),
+                range,
+            },
+            Self::Escape { character, range } => Owned::Escape { character, range },
+            Self::Whitespace { range } => Owned::Whitespace { range },
+            Self::SoftBreak { range } => Owned::SoftBreak { range },
+            Self::HardBreak { range } => Owned::HardBreak { range },
+            Self::Bold {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Bold {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Italic {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Italic {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Underline {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Underline {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Strikethrough {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Strikethrough {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Verbatim {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Verbatim {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Spoiler {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Spoiler {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Superscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Superscript {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Subscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Subscript {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::InlineMath {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::InlineMath {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Variable {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::Variable {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::NullModifier {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => Owned::NullModifier {
+                markup: owned_inlines(markup),
+                attrs,
+                free_form,
+                range,
+            },
+            Self::Macro {
+                name,
+                markup,
+                attrs,
+                range,
+            } => Owned::Macro {
+                name: name.to_string(),
+                markup: markup.map(owned_inlines),
+                attrs: attrs.map(|attrs| attrs.into_iter().map(str::to_string).collect()),
+                range,
+            },
+            Self::Link {
+                target,
+                markup,
+                attrs,
+                range,
+            } => Owned::Link {
+                target,
+                markup: markup.map(owned_inlines),
+                attrs,
+                range,
+            },
+            Self::Anchor {
+                target,
+                markup,
+                id,
+                attrs,
+                range,
+            } => Owned::Anchor {
+                target,
+                markup: owned_inlines(markup),
+                id,
+                attrs,
+                range,
+            },
+        }
+    }
+}
+
+fn owned_blocks(blocks: Vec<NorgBlock>) -> Vec<block::NorgBlock> {
+    blocks.into_iter().map(NorgBlock::into_owned).collect()
+}
+
+fn owned_items(items: Vec<ListItem>) -> Vec<block::ListItem> {
+    items.into_iter().map(ListItem::into_owned).collect()
+}
+
+fn owned_inlines(inlines: Vec<NorgInline>) -> Vec<inline::NorgInline> {
+    inlines.into_iter().map(NorgInline::into_owned).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_borrows_source() {
+        let source = "* heading\n  some /text/\n";
+        let ast = parser::try_parse_borrowed(source.as_bytes()).unwrap();
+        let NorgBlock::Section { contents, .. } = &ast.blocks[0] else {
+            panic!("expected section, got {:?}", ast.blocks[0]);
+        };
+        let NorgBlock::Paragraph { inlines, .. } = &contents[0] else {
+            panic!("expected paragraph, got {:?}", contents[0]);
+        };
+        let NorgInline::Text { text, range } = &inlines[0] else {
+            panic!("expected text, got {:?}", inlines[0]);
+        };
+        assert_eq!(*text, "some");
+        assert!(std::ptr::eq(*text, &source[range.start..range.end]));
+        assert_eq!(ast.into_owned().blocks, parser::parse(source.as_bytes()).blocks);
+    }
+}
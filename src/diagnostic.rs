@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+use crate::parser::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub enum Severity {
+    /// source couldn't be parsed as written; some content is missing from the AST
+    Error,
+    /// source was parsed, but some of it was dropped or may not mean what the author intended
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub range: Range,
+    /// syntax node kind this diagnostic is reported on, named after tree-sitter-norg nodes
+    pub kind: String,
+    /// source at `range` is missing from the AST, so writing the AST back loses it
+    #[serde(default)]
+    pub dropped: bool,
+}
+
+#[cfg(feature = "tree-sitter")]
+impl Diagnostic {
+    pub(crate) fn error(message: impl Into<String>, node: tree_sitter::Node) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            range: node.range().into(),
+            kind: node.kind().to_string(),
+            dropped: false,
+        }
+    }
+
+    pub(crate) fn warning(message: impl Into<String>, node: tree_sitter::Node) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            range: node.range().into(),
+            kind: node.kind().to_string(),
+            dropped: false,
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::parser::{parse_tstree, NorgAST, ParseError, Point};
+
+/// Text edit as reported by editors: replaced byte range with its line/column positions, and
+/// the text inserted in its place.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub start_byte: usize,
+    pub old_end_byte: usize,
+    pub start_point: Point,
+    pub old_end_point: Point,
+    pub text: String,
+}
+
+impl TextEdit {
+    fn new_end_byte(&self) -> usize {
+        self.start_byte + self.text.len()
+    }
+
+    fn new_end_point(&self) -> Point {
+        match self.text.rfind('\n') {
+            Some(last_newline) => Point {
+                row: self.start_point.row + self.text.matches('\n').count(),
+                column: self.text.len() - last_newline - 1,
+            },
+            None => Point {
+                row: self.start_point.row,
+                column: self.start_point.column + self.text.len(),
+            },
+        }
+    }
+}
+
+impl From<&TextEdit> for tree_sitter::InputEdit {
+    fn from(edit: &TextEdit) -> Self {
+        Self {
+            start_byte: edit.start_byte,
+            old_end_byte: edit.old_end_byte,
+            new_end_byte: edit.new_end_byte(),
+            start_position: edit.start_point.into(),
+            old_end_position: edit.old_end_point.into(),
+            new_end_position: edit.new_end_point().into(),
+        }
+    }
+}
+
+/// Norg document kept in sync with editor changes.
+///
+/// Holds the text and the previous tree-sitter tree so each edit only reparses the changed
+/// region.
+pub struct NorgDocument {
+    parser: tree_sitter::Parser,
+    text: Vec<u8>,
+    tree: tree_sitter::Tree,
+    ast: NorgAST,
+}
+
+impl NorgDocument {
+    pub fn new(text: impl Into<Vec<u8>>) -> Result<Self, ParseError> {
+        let text = text.into();
+        let mut parser = tree_sitter::Parser::new();
+        let language = tree_sitter_norg::LANGUAGE;
+        parser.set_language(&language.into())?;
+        let tree = parser.parse(&text, None).ok_or(ParseError::Aborted)?;
+        let ast = parse_tstree(&tree, &text)?;
+        Ok(Self {
+            parser,
+            text,
+            tree,
+            ast,
+        })
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn tree(&self) -> &tree_sitter::Tree {
+        &self.tree
+    }
+
+    pub fn ast(&self) -> &NorgAST {
+        &self.ast
+    }
+
+    fn is_valid_edit(&self, edit: &TextEdit) -> bool {
+        // utf-8 continuation bytes are `0b10xxxxxx`
+        let is_boundary = |i: usize| self.text.get(i).is_none_or(|&b| b & 0xC0 != 0x80);
+        edit.start_byte <= edit.old_end_byte
+            && edit.old_end_byte <= self.text.len()
+            && is_boundary(edit.start_byte)
+            && is_boundary(edit.old_end_byte)
+    }
+
+    /// Apply `edits` in order and reparse the document once.
+    ///
+    /// Each edit is expressed in the coordinates of the text produced by the previous one, the
+    /// same way LSP `didChange` notifications report them. Returns the updated AST and the
+    /// indices of top-level blocks in it that overlap changed text.
+    ///
+    /// On error the text and tree keep the edits but the AST is left as it was. An edit out of
+    /// range of the text or not on char boundaries stops there with
+    /// [`ParseError::InvalidEdit`], keeping the edits before it.
+    pub fn edit(&mut self, edits: &[TextEdit]) -> Result<(&NorgAST, Vec<usize>), ParseError> {
+        // byte ranges touched by the edits, in the coordinates of the final text
+        let mut edited: Vec<(usize, usize)> = vec![];
+        for edit in edits {
+            if !self.is_valid_edit(edit) {
+                return Err(ParseError::InvalidEdit {
+                    start_byte: edit.start_byte,
+                    old_end_byte: edit.old_end_byte,
+                });
+            }
+            self.text
+                .splice(edit.start_byte..edit.old_end_byte, edit.text.bytes());
+            self.tree.edit(&edit.into());
+            let new_end = edit.new_end_byte();
+            for (start, end) in edited.iter_mut() {
+                if *start >= edit.old_end_byte {
+                    *start = *start + new_end - edit.old_end_byte;
+                    *end = *end + new_end - edit.old_end_byte;
+                } else if *end >= edit.start_byte {
+                    *start = (*start).min(edit.start_byte);
+                    *end = (*end).max(edit.old_end_byte) + new_end - edit.old_end_byte;
+                }
+            }
+            edited.push((edit.start_byte, new_end));
+        }
+        let tree = self
+            .parser
+            .parse(&self.text, Some(&self.tree))
+            .ok_or(ParseError::Aborted)?;
+        edited.extend(
+            self.tree
+                .changed_ranges(&tree)
+                .map(|range| (range.start_byte, range.end_byte)),
+        );
+        self.tree = tree;
+        self.ast = parse_tstree(&self.tree, &self.text)?;
+        let changed = self
+            .ast
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| {
+                let range = block.range();
+                // pure deletions leave an empty range; count the block containing it
+                edited
+                    .iter()
+                    .any(|&(start, end)| range.start < end.max(start + 1) && start < range.end)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        Ok((&self.ast, changed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incremental_edit_matches_full_parse() {
+        let mut doc = NorgDocument::new("first paragraph\n\nsecond paragraph\n").unwrap();
+        let (ast, changed) = doc.edit(&[TextEdit {
+            start_byte: 17,
+            old_end_byte: 23,
+            start_point: Point { row: 2, column: 0 },
+            old_end_point: Point { row: 2, column: 6 },
+            text: String::from("edited"),
+        }])
+        .unwrap();
+        assert_eq!(changed, vec![1]);
+        let expected = crate::parser::parse(b"first paragraph\n\nedited paragraph\n");
+        assert_eq!(ast.blocks, expected.blocks);
+    }
+
+    #[test]
+    fn test_invalid_edit() {
+        let mut doc = NorgDocument::new("añb\n").unwrap();
+        let edit = |start_byte, old_end_byte| TextEdit {
+            start_byte,
+            old_end_byte,
+            start_point: Point::default(),
+            old_end_point: Point::default(),
+            text: String::new(),
+        };
+        for edit in [edit(2, 3), edit(3, 10), edit(3, 1)] {
+            assert!(matches!(
+                doc.edit(&[edit]),
+                Err(ParseError::InvalidEdit { .. })
+            ));
+        }
+        assert_eq!(doc.text(), "añb\n".as_bytes());
+    }
+}
@@ -124,11 +124,41 @@ impl Exporter {
                 TaggedJanet::String(s) => s.as_bytes().to_vec(),
                 _ => unreachable!("Already checked to be a buffer|string"),
             };
-            let ast = crate::parser::parse(&content);
-            Janet::structs(ast.into())
+            thread_local! {
+                // embedded documents are parsed on every export, so the grammar is loaded once
+                static PARSER: std::cell::RefCell<Option<crate::parser::NorgParser>> =
+                    const { std::cell::RefCell::new(None) };
+            }
+            let ast = PARSER.with_borrow_mut(|parser| {
+                let parser = match parser {
+                    Some(parser) => parser,
+                    None => parser.insert(crate::parser::NorgParser::new()?),
+                };
+                parser.parse(&content)
+            });
+            match ast {
+                Ok(ast) => Janet::structs(ast.into()),
+                Err(err) => janetrs::jpanic!("norg/parse: {err}"),
+            }
+        }
+
+        #[janetrs::janet_fn(arity(fix(1)))]
+        fn norg_slug(args: &mut [Janet]) -> Janet {
+            use janetrs::JanetArgs as _;
+            use janetrs::{JanetType, TaggedJanet};
+
+            let text = match args.get_tagged_matches(0, &[JanetType::Buffer, JanetType::String]) {
+                TaggedJanet::Buffer(b) => b.to_string(),
+                TaggedJanet::String(s) => s.to_string(),
+                _ => unreachable!("Already checked to be a buffer|string"),
+            };
+            Janet::from(crate::slug::slugify(&text).as_str())
         }
 
         janet_client.add_c_fn(CFunOptions::new(c"norg/parse", norg_parse_c));
+        janet_client.add_c_fn(CFunOptions::new(c"norg/slugify", norg_slug_c));
+        // stdlib calls the `norg/slug` var, which only exists as nil when the image is built
+        janet_client.run("(set norg/slug norg/slugify)").unwrap();
         Self { janet_client }
     }
 
@@ -0,0 +1,345 @@
+//! Canonical formatting of norg source.
+//!
+//! Source is parsed and written back with [`crate::writer`], so headings, list prefixes,
+//! attributes and blank lines between blocks all come out in one style. Nested list items are
+//! written flush left as their level is in the prefix, and their continuation lines are
+//! indented to the item text. Whitespace around line breaks is dropped, and paragraphs can be
+//! reflowed to a width. Content of ranged tags like `@code` and verbatim inlines is kept as is.
+
+use crate::{
+    block::{self, Fold, Heading, ListItem, NorgBlock, TableCell},
+    diagnostic::Diagnostic,
+    inline::NorgInline,
+    parser::{self, ParseError},
+    writer,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// reflow paragraphs to this many columns. Line breaks are kept when `None`
+    pub width: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Parse(ParseError),
+    /// source has problems, and formatting would drop the content they point at
+    Diagnostics(Vec<Diagnostic>),
+}
+
+impl From<ParseError> for FormatError {
+    fn from(value: ParseError) -> Self {
+        Self::Parse(value)
+    }
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(err) => err.fmt(f),
+            Self::Diagnostics(diagnostics) => {
+                write!(
+                    f,
+                    "can't format source with {} problem(s)",
+                    diagnostics.len()
+                )?;
+                for diagnostic in diagnostics {
+                    let point = diagnostic.range.start_point;
+                    write!(
+                        f,
+                        "\n{}:{}: {}",
+                        point.row + 1,
+                        point.column + 1,
+                        diagnostic.message
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Format norg source. Formatting the result again gives the same text.
+pub fn format(text: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    let ast = parser::try_parse(text.as_bytes())?;
+    let dropped: Vec<_> = ast
+        .diagnostics
+        .into_iter()
+        .filter(|diagnostic| diagnostic.dropped)
+        .collect();
+    if !dropped.is_empty() {
+        return Err(FormatError::Diagnostics(dropped));
+    }
+    let mut layout = Layout {
+        source: text,
+        width: options.width,
+        level: 0,
+        prefix: None,
+    };
+    let blocks: Vec<_> = ast
+        .blocks
+        .into_iter()
+        .map(|block| layout.fold_block(block))
+        .collect();
+    Ok(writer::blocks_to_norg(&blocks))
+}
+
+struct Layout<'a> {
+    source: &'a str,
+    width: Option<usize>,
+    /// level of the list being folded
+    level: usize,
+    /// columns before the first and following lines of the next paragraph, set by list items
+    prefix: Option<(usize, usize)>,
+}
+
+impl Fold for Layout<'_> {
+    fn fold_block(&mut self, block: NorgBlock) -> NorgBlock {
+        use NorgBlock::*;
+        match block {
+            Paragraph {
+                attrs,
+                inlines,
+                range,
+            } => {
+                let (first, rest) = self.prefix.take().unwrap_or_default();
+                let inlines = self.fold_inlines(inlines, true);
+                let inlines = match self.width {
+                    Some(width) => reflow(inlines, width, first, rest),
+                    None => inlines,
+                };
+                let inlines = if rest > 0 {
+                    indent_lines(inlines)
+                } else {
+                    inlines
+                };
+                Paragraph {
+                    attrs,
+                    inlines,
+                    range,
+                }
+            }
+            UnorderedList { level, .. } | OrderedList { level, .. } | Quote { level, .. } => {
+                let outer = std::mem::replace(&mut self.level, level as usize);
+                let block = block::fold_block(self, block);
+                self.level = outer;
+                block
+            }
+            Definition { .. } | Footnote { .. } => {
+                let mut block = block::fold_block(self, block);
+                if let Definition { title, .. } | Footnote { title, .. } = &mut block {
+                    *title = collapse_whitespace(std::mem::take(title), true);
+                }
+                block
+            }
+            block => block::fold_block(self, block),
+        }
+    }
+
+    fn fold_heading(&mut self, mut heading: Heading) -> Heading {
+        heading.inlines = self.fold_inlines(heading.inlines, true);
+        heading
+    }
+
+    fn fold_list_item(&mut self, item: ListItem) -> ListItem {
+        // `-- ` prefix, then attributes on the first line
+        let indent = self.level + 1;
+        let first = if item.attrs.is_empty() {
+            indent
+        } else {
+            indent + writer::attrs_to_norg(&item.attrs).chars().count() + 1
+        };
+        self.prefix = Some((first, indent));
+        let item = block::fold_list_item(self, item);
+        self.prefix = None;
+        item
+    }
+
+    fn fold_table_cell(&mut self, mut cell: TableCell) -> TableCell {
+        cell.inlines = self.fold_inlines(cell.inlines, true);
+        cell
+    }
+
+    fn fold_inline(&mut self, mut inline: NorgInline) -> NorgInline {
+        use NorgInline::*;
+        match inline {
+            // AST doesn't keep the width of whitespace, so take verbatim content from source
+            Verbatim { ref mut markup, .. }
+            | InlineMath { ref mut markup, .. }
+            | Variable { ref mut markup, .. } => {
+                if let (Some(first), Some(last)) = (markup.first(), markup.last()) {
+                    let range = first.range().join(last.range());
+                    let text = self.source[range.start..range.end].to_string();
+                    *markup = vec![Text { text, range }];
+                }
+                inline
+            }
+            _ => {
+                if let Some(markup) = inline.markup_mut() {
+                    *markup = self.fold_inlines(std::mem::take(markup), false);
+                }
+                inline
+            }
+        }
+    }
+}
+
+impl Layout<'_> {
+    fn fold_inlines(&mut self, inlines: Vec<NorgInline>, trim: bool) -> Vec<NorgInline> {
+        let inlines = inlines
+            .into_iter()
+            .map(|inline| self.fold_inline(inline))
+            .collect();
+        collapse_whitespace(inlines, trim)
+    }
+}
+
+fn is_space(inline: &NorgInline) -> bool {
+    matches!(
+        inline,
+        NorgInline::Whitespace { .. } | NorgInline::SoftBreak { .. }
+    )
+}
+
+/// Merge whitespace and soft breaks into one, keeping the break. Whitespace after a hard break
+/// and before one is dropped, and so is whitespace at both ends when `trim` is set.
+fn collapse_whitespace(inlines: Vec<NorgInline>, trim: bool) -> Vec<NorgInline> {
+    let mut out: Vec<NorgInline> = vec![];
+    let mut space: Option<NorgInline> = None;
+    for inline in inlines {
+        if is_space(&inline) {
+            let after_break = matches!(out.last(), Some(NorgInline::HardBreak { .. }));
+            if !after_break && !matches!(space, Some(NorgInline::SoftBreak { .. })) {
+                space = Some(inline);
+            }
+            continue;
+        }
+        if let Some(space) = space.take() {
+            let leading = trim && out.is_empty();
+            if !leading && !matches!(inline, NorgInline::HardBreak { .. }) {
+                out.push(space);
+            }
+        }
+        out.push(inline);
+    }
+    if !trim {
+        out.extend(space);
+    }
+    out
+}
+
+/// Put whitespace after every line break, which the writer turns into indentation to the list
+/// item text.
+fn indent_lines(inlines: Vec<NorgInline>) -> Vec<NorgInline> {
+    let mut out = Vec::with_capacity(inlines.len());
+    for inline in inlines {
+        let range = inline.range();
+        let line_break = matches!(
+            inline,
+            NorgInline::SoftBreak { .. } | NorgInline::HardBreak { .. }
+        );
+        out.push(inline);
+        if line_break {
+            out.push(NorgInline::Whitespace { range });
+        }
+    }
+    out
+}
+
+/// Break paragraph lines at whitespace so they fit in `width` columns where possible. The first
+/// line starts at column `first` and the rest at `rest`.
+fn reflow(inlines: Vec<NorgInline>, width: usize, first: usize, rest: usize) -> Vec<NorgInline> {
+    let mut out = vec![];
+    let mut column = first;
+    let mut line_start = true;
+    let mut inlines = inlines.into_iter().peekable();
+    while let Some(inline) = inlines.next() {
+        if matches!(inline, NorgInline::HardBreak { .. }) {
+            out.push(inline);
+            column = rest;
+            line_start = true;
+            continue;
+        }
+        let space = is_space(&inline).then_some(inline.range());
+        let mut word = vec![];
+        if space.is_none() {
+            word.push(inline);
+        }
+        while let Some(inline) = inlines
+            .next_if(|inline| !is_space(inline) && !matches!(inline, NorgInline::HardBreak { .. }))
+        {
+            word.push(inline);
+        }
+        let text = writer::inlines_to_norg(&word);
+        let len = text.chars().count();
+        if let (false, Some(range)) = (line_start, space) {
+            if column + 1 + len > width && !starts_block(&text) {
+                out.push(NorgInline::SoftBreak { range });
+                column = rest;
+            } else {
+                out.push(NorgInline::Whitespace { range });
+                column += 1;
+            }
+        }
+        column += len;
+        line_start = false;
+        out.extend(word);
+    }
+    out
+}
+
+/// whether a line starting with `word` could be read as a block, like a tag or list item
+fn starts_block(word: &str) -> bool {
+    let Some(first) = word.chars().next() else {
+        return false;
+    };
+    matches!(first, '@' | '.' | '#' | '+' | '|')
+        || matches!(first, '*' | '-' | '~' | '>' | '$' | '^' | '_' | '=')
+            && word.chars().all(|c| c == first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let text = "*   heading  \n  paragraph   with \n   lines  \n\n\n-  (x)   item\n  --  nested\n\n@code\n  keep   this  \n@end\n";
+        let formatted = format(text, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            formatted,
+            "* heading\n\nparagraph with\nlines\n\n- (x) item\n-- nested\n\n@code\n  keep   this  \n@end\n"
+        );
+        assert_eq!(
+            format(&formatted, &FormatOptions::default()).unwrap(),
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_format_reflow() {
+        let options = FormatOptions { width: Some(20) };
+        let text =
+            "one two three fourth - five `six   seven`\n\n- an item with a long line of text\n";
+        let formatted = format(text, &options).unwrap();
+        assert_eq!(
+            formatted,
+            "one two three fourth -\nfive `six   seven`\n\n- an item with a\n  long line of text\n"
+        );
+        assert_eq!(format(&formatted, &options).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_diagnostics() {
+        let options = FormatOptions::default();
+        // kept as a plain ranged tag
+        let text = "@table\nnot a table\n@end\n";
+        assert_eq!(format(text, &options).unwrap(), text);
+        let Err(FormatError::Diagnostics(diagnostics)) = format("text\n\n#tag\n", &options) else {
+            panic!("expected dropped carryover tag to fail formatting");
+        };
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
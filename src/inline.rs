@@ -1,30 +1,43 @@
 #![allow(unused_variables)]
 
+#[cfg(feature = "janet")]
 use janetrs::{
     Janet, JanetArray, JanetConversionError, JanetKeyword, JanetString, JanetStruct, JanetTuple,
     JanetType, TaggedJanet,
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    block::{Fold, Visit, VisitMut},
+    parser::Range,
+    target::NorgLinkTarget,
+};
+#[cfg(feature = "janet")]
+use crate::slug::{markup_text, slugify};
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum Attribute {
     Blank,
     Key(String),
     KeyValue(String, String),
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<JanetTuple<'_>> for Attribute {
     type Error = JanetConversionError;
     fn try_from(value: JanetTuple) -> Result<Self, Self::Error> {
         todo!()
     }
 }
+#[cfg(feature = "janet")]
 impl TryFrom<JanetArray<'_>> for Attribute {
     type Error = JanetConversionError;
     fn try_from(value: JanetArray) -> Result<Self, Self::Error> {
         todo!()
     }
 }
+#[cfg(feature = "janet")]
 impl TryFrom<Janet> for Attribute {
     type Error = JanetConversionError;
 
@@ -42,6 +55,7 @@ impl TryFrom<Janet> for Attribute {
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<JanetTuple<'_>> for Attribute {
     fn into(self) -> JanetTuple<'static> {
         match self {
@@ -54,61 +68,255 @@ impl Into<JanetTuple<'_>> for Attribute {
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<Janet> for Attribute {
     fn into(self) -> Janet {
         Janet::tuple(self.into())
     }
 }
 
+// `S` is the type of source text, `&str` slices in [`crate::borrowed`] ASTs
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
-pub enum NorgInline {
-    Text(String),
-    Special(String),
-    Escape(char),
-    Whitespace,
-    SoftBreak,
-    HardBreak,
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema, ts_rs::TS),
+    schemars(rename = "NorgInline"),
+    ts(concrete(S = String))
+)]
+pub enum NorgInline<S = String> {
+    Text {
+        text: S,
+        range: Range,
+    },
+    Special {
+        text: S,
+        range: Range,
+    },
+    Escape {
+        character: char,
+        range: Range,
+    },
+    Whitespace {
+        range: Range,
+    },
+    SoftBreak {
+        range: Range,
+    },
+    HardBreak {
+        range: Range,
+    },
     Bold {
         markup: Vec<Self>,
         attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
     },
     Italic {
         markup: Vec<Self>,
         attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
     },
     Underline {
         markup: Vec<Self>,
         attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
     },
     Strikethrough {
         markup: Vec<Self>,
         attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
     },
     Verbatim {
         markup: Vec<Self>,
         attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    Spoiler {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    Superscript {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    Subscript {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    /// `$x^2$`, content is raw math source
+    InlineMath {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    Variable {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
+    },
+    /// `%comment%`, never exported
+    NullModifier {
+        markup: Vec<Self>,
+        attrs: Vec<Attribute>,
+        /// written in free-form (`X| ... |X`)
+        free_form: bool,
+        range: Range,
     },
     // TODO: rename this to "InlineTag"
     Macro {
-        name: String,
-        markup: Option<Vec<NorgInline>>,
-        attrs: Option<Vec<String>>,
+        name: S,
+        markup: Option<Vec<Self>>,
+        attrs: Option<Vec<S>>,
         // TODO: add attributes and markup parameter
+        range: Range,
     },
     Link {
-        target: String,
+        target: NorgLinkTarget,
         markup: Option<Vec<Self>>,
         attrs: Vec<Attribute>,
+        range: Range,
     },
     Anchor {
-        target: Option<String>,
+        target: Option<NorgLinkTarget>,
         markup: Vec<Self>,
-        hash: u64,
+        /// slug of the markup, shared by anchor definition and its references
+        id: String,
         attrs: Vec<Attribute>,
+        range: Range,
     },
     // TODO: embed
 }
 
+impl<S> NorgInline<S> {
+    /// source span of this inline. Inlines created from janet have default (empty) span.
+    pub fn range(&self) -> Range {
+        use NorgInline::*;
+        match self {
+            Text { range, .. }
+            | Special { range, .. }
+            | Escape { range, .. }
+            | Whitespace { range }
+            | SoftBreak { range }
+            | HardBreak { range }
+            | Bold { range, .. }
+            | Italic { range, .. }
+            | Underline { range, .. }
+            | Strikethrough { range, .. }
+            | Verbatim { range, .. }
+            | Spoiler { range, .. }
+            | Superscript { range, .. }
+            | Subscript { range, .. }
+            | InlineMath { range, .. }
+            | Variable { range, .. }
+            | NullModifier { range, .. }
+            | Macro { range, .. }
+            | Link { range, .. }
+            | Anchor { range, .. } => *range,
+        }
+    }
+
+    /// nested markup of this inline, empty for leaf inlines
+    pub fn markup(&self) -> &[Self] {
+        use NorgInline::*;
+        match self {
+            Bold { markup, .. }
+            | Italic { markup, .. }
+            | Underline { markup, .. }
+            | Strikethrough { markup, .. }
+            | Verbatim { markup, .. }
+            | Spoiler { markup, .. }
+            | Superscript { markup, .. }
+            | Subscript { markup, .. }
+            | InlineMath { markup, .. }
+            | Variable { markup, .. }
+            | NullModifier { markup, .. }
+            | Anchor { markup, .. } => markup,
+            Macro { markup, .. } | Link { markup, .. } => markup.as_deref().unwrap_or_default(),
+            Text { .. }
+            | Special { .. }
+            | Escape { .. }
+            | Whitespace { .. }
+            | SoftBreak { .. }
+            | HardBreak { .. } => &[],
+        }
+    }
+
+    /// nested markup of this inline, `None` for leaf inlines
+    pub fn markup_mut(&mut self) -> Option<&mut Vec<Self>> {
+        use NorgInline::*;
+        match self {
+            Bold { markup, .. }
+            | Italic { markup, .. }
+            | Underline { markup, .. }
+            | Strikethrough { markup, .. }
+            | Verbatim { markup, .. }
+            | Spoiler { markup, .. }
+            | Superscript { markup, .. }
+            | Subscript { markup, .. }
+            | InlineMath { markup, .. }
+            | Variable { markup, .. }
+            | NullModifier { markup, .. }
+            | Anchor { markup, .. } => Some(markup),
+            Macro { markup, .. } | Link { markup, .. } => markup.as_mut(),
+            Text { .. }
+            | Special { .. }
+            | Escape { .. }
+            | Whitespace { .. }
+            | SoftBreak { .. }
+            | HardBreak { .. } => None,
+        }
+    }
+}
+
+/// Visit nested markup of `inline`, see [`Visit`].
+pub fn visit_inline<V: Visit + ?Sized>(v: &mut V, inline: &NorgInline) {
+    for inline in inline.markup() {
+        v.visit_inline(inline);
+    }
+}
+
+/// Visit nested markup of `inline`, see [`VisitMut`].
+pub fn visit_inline_mut<V: VisitMut + ?Sized>(v: &mut V, inline: &mut NorgInline) {
+    if let Some(markup) = inline.markup_mut() {
+        for inline in markup {
+            v.visit_inline_mut(inline);
+        }
+    }
+}
+
+/// Fold nested markup of `inline`, see [`Fold`].
+pub fn fold_inline<F: Fold + ?Sized>(f: &mut F, mut inline: NorgInline) -> NorgInline {
+    if let Some(markup) = inline.markup_mut() {
+        *markup = std::mem::take(markup)
+            .into_iter()
+            .map(|inline| f.fold_inline(inline))
+            .collect();
+    }
+    inline
+}
+
 // IF abstract objects are janet abstact type
 // - no need to serialize
 // - have to implement method to get all properties
@@ -116,6 +324,7 @@ pub enum NorgInline {
 // IF abstract objects can be represented in janet struct type
 // - need to implement serializing logic for EVERY objects
 
+#[cfg(feature = "janet")]
 impl TryFrom<Janet> for NorgInline {
     // TODO: use actual error instead
     type Error = JanetConversionError;
@@ -128,33 +337,38 @@ impl TryFrom<Janet> for NorgInline {
             .get_owned(JanetKeyword::new(b"kind"))
             .ok_or(JanetConversionError::Other)?
             .try_into()?;
+        let range = Range::from_janet_node(&value)?;
         match kind.as_bytes() {
-            b"whitespace" => Ok(NorgInline::Whitespace),
-            b"softbreak" => Ok(NorgInline::SoftBreak),
-            b"hardbreak" => Ok(NorgInline::HardBreak),
-            b"text" => Ok(NorgInline::Text(
-                value
+            b"whitespace" => Ok(NorgInline::Whitespace { range }),
+            b"softbreak" => Ok(NorgInline::SoftBreak { range }),
+            b"hardbreak" => Ok(NorgInline::HardBreak { range }),
+            b"text" => Ok(NorgInline::Text {
+                text: value
                     .get_owned(JanetKeyword::new(b"text"))
                     .ok_or(JanetConversionError::Other)?
                     .try_unwrap::<JanetString>()?
                     .to_string(),
-            )),
-            b"special" => Ok(NorgInline::Special(
-                value
-                    .get_owned(JanetKeyword::new(b"text"))
+                range,
+            }),
+            b"special" => Ok(NorgInline::Special {
+                text: value
+                    .get_owned(JanetKeyword::new(b"special"))
                     .ok_or(JanetConversionError::Other)?
                     .try_unwrap::<JanetString>()?
                     .to_string(),
-            )),
-            b"escape" => Ok(NorgInline::Escape(
-                value
+                range,
+            }),
+            b"escape" => Ok(NorgInline::Escape {
+                character: value
                     .get_owned(JanetKeyword::new(b"escape"))
                     .ok_or(JanetConversionError::Other)?
                     .try_unwrap::<u32>()?
                     .try_into()
                     .unwrap(),
-            )),
-            b"bold" | b"italic" | b"underline" | b"strikethrough" | b"verbatim" => {
+                range,
+            }),
+            b"bold" | b"italic" | b"underline" | b"strikethrough" | b"verbatim" | b"spoiler"
+            | b"superscript" | b"subscript" | b"inline-math" | b"variable" | b"null-modifier" => {
                 let markup = value
                     .get(JanetKeyword::new(b"markup"))
                     .ok_or(JanetConversionError::Other)?;
@@ -178,12 +392,21 @@ impl TryFrom<Janet> for NorgInline {
                     .get(JanetKeyword::new(b"attrs"))
                     .ok_or(JanetConversionError::Other)?;
                 let attrs = vec![];
+                let free_form = value
+                    .get(JanetKeyword::new(b"free-form"))
+                    .is_some_and(|free_form| free_form.is_truthy());
                 match kind.as_bytes() {
-                    b"bold" => Ok(NorgInline::Bold { markup, attrs }),
-                    b"italic" => Ok(NorgInline::Italic { markup, attrs }),
-                    b"underline" => Ok(NorgInline::Underline { markup, attrs }),
-                    b"strikethrough" => Ok(NorgInline::Strikethrough { markup, attrs }),
-                    b"verbatim" => Ok(NorgInline::Verbatim { markup, attrs }),
+                    b"bold" => Ok(NorgInline::Bold { markup, attrs, free_form, range }),
+                    b"italic" => Ok(NorgInline::Italic { markup, attrs, free_form, range }),
+                    b"underline" => Ok(NorgInline::Underline { markup, attrs, free_form, range }),
+                    b"strikethrough" => Ok(NorgInline::Strikethrough { markup, attrs, free_form, range }),
+                    b"verbatim" => Ok(NorgInline::Verbatim { markup, attrs, free_form, range }),
+                    b"spoiler" => Ok(NorgInline::Spoiler { markup, attrs, free_form, range }),
+                    b"superscript" => Ok(NorgInline::Superscript { markup, attrs, free_form, range }),
+                    b"subscript" => Ok(NorgInline::Subscript { markup, attrs, free_form, range }),
+                    b"inline-math" => Ok(NorgInline::InlineMath { markup, attrs, free_form, range }),
+                    b"variable" => Ok(NorgInline::Variable { markup, attrs, free_form, range }),
+                    b"null-modifier" => Ok(NorgInline::NullModifier { markup, attrs, free_form, range }),
                     _ => unreachable!(),
                 }
             }
@@ -219,15 +442,14 @@ impl TryFrom<Janet> for NorgInline {
                         _ => None,
                     })
                     .transpose()?,
+                range,
             }),
             b"link" | b"anchor" => {
-                let target =
-                    value.get(JanetKeyword::new(b"target")).and_then(|target| {
-                        match target.unwrap() {
-                            TaggedJanet::String(target) => Some(target.to_string()),
-                            _ => None,
-                        }
-                    });
+                let target = value
+                    .get_owned(JanetKeyword::new(b"target"))
+                    .filter(|target| !target.is_nil())
+                    .map(NorgLinkTarget::try_from)
+                    .transpose()?;
                 let markup = value.get(JanetKeyword::new(b"markup")).and_then(|inlines| {
                     match inlines.unwrap() {
                         TaggedJanet::Tuple(inlines) => Some(
@@ -245,21 +467,23 @@ impl TryFrom<Janet> for NorgInline {
                         target: target.ok_or(JanetConversionError::Other)?,
                         markup,
                         attrs,
+                        range,
                     }),
-                    b"anchor" => Ok(NorgInline::Anchor {
-                        target,
-                        markup: markup.ok_or(JanetConversionError::Other)?,
-                        hash: {
-                            let hash = value
-                                .get(JanetKeyword::new(b"hash"))
-                                .ok_or(JanetConversionError::Other)?;
-                            match hash.unwrap() {
-                                TaggedJanet::Number(num) => num as u64,
-                                _ => todo!("error"),
-                            }
-                        },
-                        attrs,
-                    }),
+                    b"anchor" => {
+                        let markup = markup.ok_or(JanetConversionError::Other)?;
+                        // anchors made in janet may not have an id
+                        let id = match value.get_owned(JanetKeyword::new(b"id")) {
+                            Some(id) => id.try_unwrap::<JanetString>()?.to_string(),
+                            None => slugify(&markup_text(&markup)),
+                        };
+                        Ok(NorgInline::Anchor {
+                            target,
+                            markup,
+                            id,
+                            attrs,
+                            range,
+                        })
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -268,32 +492,44 @@ impl TryFrom<Janet> for NorgInline {
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<Janet> for NorgInline {
     fn into(self) -> Janet {
         use crate::inline::NorgInline::*;
         let st = match self {
-            Whitespace => JanetStruct::builder(1)
+            Whitespace { range } => JanetStruct::builder(2)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"whitespace"))
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            SoftBreak => JanetStruct::builder(1)
+            SoftBreak { range } => JanetStruct::builder(2)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"softbreak"))
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            HardBreak => JanetStruct::builder(1)
+            HardBreak { range } => JanetStruct::builder(2)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"hardbreak"))
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Text(text) => JanetStruct::builder(2)
+            Text { text, range } => JanetStruct::builder(3)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"text"))
                 .put(JanetKeyword::new(b"text"), JanetString::new(&text))
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Special(text) => JanetStruct::builder(2)
+            Special { text, range } => JanetStruct::builder(3)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"special"))
                 .put(JanetKeyword::new(b"special"), JanetString::new(&text))
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Escape(c) => JanetStruct::builder(2)
+            Escape { character, range } => JanetStruct::builder(3)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"escape"))
-                .put(JanetKeyword::new(b"escape"), c)
+                .put(JanetKeyword::new(b"escape"), character)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Bold { markup, attrs } => JanetStruct::builder(3)
+            Bold {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"bold"))
                 .put(
                     JanetKeyword::new(b"markup"),
@@ -303,8 +539,15 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Italic { markup, attrs } => JanetStruct::builder(3)
+            Italic {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"italic"))
                 .put(
                     JanetKeyword::new(b"markup"),
@@ -314,8 +557,15 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Underline { markup, attrs } => JanetStruct::builder(3)
+            Underline {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"underline"))
                 .put(
                     JanetKeyword::new(b"markup"),
@@ -325,8 +575,15 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Strikethrough { markup, attrs } => JanetStruct::builder(3)
+            Strikethrough {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
                 .put(
                     JanetKeyword::new(b"kind"),
                     JanetKeyword::new(b"strikethrough"),
@@ -339,8 +596,15 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Verbatim { markup, attrs } => JanetStruct::builder(3)
+            Verbatim {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"verbatim"))
                 .put(
                     JanetKeyword::new(b"markup"),
@@ -350,8 +614,123 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
-            Macro { name, markup, attrs } => JanetStruct::builder(4)
+            Spoiler {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"spoiler"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            Superscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"superscript"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            Subscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"subscript"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            InlineMath {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"inline-math"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            Variable {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"variable"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            NullModifier {
+                markup,
+                attrs,
+                free_form,
+                range,
+            } => JanetStruct::builder(5)
+                .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"null-modifier"))
+                .put(
+                    JanetKeyword::new(b"markup"),
+                    Janet::tuple(markup.into_iter().collect()),
+                )
+                .put(
+                    JanetKeyword::new(b"attrs"),
+                    Janet::tuple(attrs.into_iter().collect()),
+                )
+                .put(JanetKeyword::new(b"free-form"), free_form)
+                .put(JanetKeyword::new(b"range"), range)
+                .finalize(),
+            Macro {
+                name,
+                markup,
+                attrs,
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"macro"))
                 .put(JanetKeyword::new(b"name"), JanetString::new(&name))
                 .put(
@@ -368,15 +747,17 @@ impl Into<Janet> for NorgInline {
                         None => Janet::nil(),
                     },
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
             Anchor {
                 target,
                 markup,
-                hash,
+                id,
                 attrs,
-            } => JanetStruct::builder(5)
+                range,
+            } => JanetStruct::builder(6)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"anchor"))
-                .put(JanetKeyword::new(b"hash"), hash)
+                .put(JanetKeyword::new(b"id"), id.as_str())
                 .put(
                     JanetKeyword::new(b"markup"),
                     Janet::tuple(markup.into_iter().collect()),
@@ -384,7 +765,7 @@ impl Into<Janet> for NorgInline {
                 .put(
                     JanetKeyword::new(b"target"),
                     match target {
-                        Some(target) => Janet::string(target.into()),
+                        Some(target) => target.into(),
                         None => Janet::nil(),
                     },
                 )
@@ -392,14 +773,16 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
             Link {
                 target,
                 markup,
                 attrs,
-            } => JanetStruct::builder(4)
+                range,
+            } => JanetStruct::builder(5)
                 .put(JanetKeyword::new(b"kind"), JanetKeyword::new(b"link"))
-                .put(JanetKeyword::new(b"target"), Janet::string(target.into()))
+                .put(JanetKeyword::new(b"target"), target)
                 .put(
                     JanetKeyword::new(b"markup"),
                     match markup {
@@ -411,6 +794,7 @@ impl Into<Janet> for NorgInline {
                     JanetKeyword::new(b"attrs"),
                     Janet::tuple(attrs.into_iter().collect()),
                 )
+                .put(JanetKeyword::new(b"range"), range)
                 .finalize(),
         };
         st.into()
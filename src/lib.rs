@@ -1,7 +1,17 @@
 pub mod block;
+pub mod borrowed;
+pub mod diagnostic;
+#[cfg(feature = "tree-sitter")]
+pub mod document;
+#[cfg(feature = "janet")]
 pub mod export;
+pub mod format;
 pub mod inline;
 pub mod meta;
 pub mod parser;
-// pub mod parser2;
+pub mod parser2;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod slug;
 pub mod target;
+pub mod writer;
@@ -1,9 +1,108 @@
+use std::{
+    io::{Read, Write},
+    process::ExitCode,
+};
+
+#[cfg(feature = "janet")]
 use norg_rs::export::{ExportTarget, Exporter};
+use norg_rs::format::{format, FormatOptions};
+
+const FMT_USAGE: &str = "usage: norg-rs fmt [--check] [--width N] [FILE...]";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("fmt") => fmt(args.collect()),
+        #[cfg(feature = "janet")]
+        _ => {
+            let text = std::fs::read("test2.norg").unwrap();
+            let ast = norg_rs::parser::parse(&text);
+            let mut exporter = Exporter::new();
+            let (res, _meta) = exporter.export(ExportTarget::Html, ast, None).unwrap();
+            println!("{res}");
+            ExitCode::SUCCESS
+        }
+        #[cfg(not(feature = "janet"))]
+        _ => {
+            eprintln!("{FMT_USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-fn main() {
-    let text = std::fs::read("test2.norg").unwrap();
-    let ast = norg_rs::parser::parse(&text);
-    let mut exporter = Exporter::new();
-    let (res, _meta) = exporter.export(ExportTarget::Html, ast, None).unwrap();
-    println!("{res}");
+/// Format files in place, or stdin to stdout when no file is given. With `--check`, list files
+/// that aren't formatted instead and fail if there are any.
+fn fmt(args: Vec<String>) -> ExitCode {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut files = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => match args.next().and_then(|width| width.parse().ok()) {
+                Some(width) => options.width = Some(width),
+                None => {
+                    eprintln!("{FMT_USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("{FMT_USAGE}");
+                return ExitCode::FAILURE;
+            }
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() {
+        let mut text = String::new();
+        if let Err(err) = std::io::stdin().read_to_string(&mut text) {
+            eprintln!("error reading stdin: {err}");
+            return ExitCode::FAILURE;
+        }
+        return match format(&text, &options) {
+            Ok(formatted) if check => {
+                if formatted == text {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                }
+            }
+            Ok(formatted) => {
+                std::io::stdout().write_all(formatted.as_bytes()).unwrap();
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("<stdin>: {err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let result = std::fs::read_to_string(&file)
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                let formatted = format(&text, &options).map_err(|err| err.to_string())?;
+                Ok((text, formatted))
+            });
+        match result {
+            Ok((text, formatted)) if formatted == text => {}
+            Ok(_) if check => {
+                println!("{file}");
+                status = ExitCode::FAILURE;
+            }
+            Ok((_, formatted)) => {
+                if let Err(err) = std::fs::write(&file, formatted) {
+                    eprintln!("{file}: {err}");
+                    status = ExitCode::FAILURE;
+                }
+            }
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
 }
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 
+#[cfg(feature = "janet")]
 use janetrs::{Janet, JanetConversionError, JanetString, TaggedJanet};
 use serde::Serialize;
 
@@ -14,6 +15,7 @@ pub enum NorgMeta {
     Object(BTreeMap<String, NorgMeta>),
 }
 
+#[cfg(feature = "janet")]
 pub(crate) fn janetkv_to_metaobj(
     kv: impl IntoIterator<Item = (Janet, Janet)>,
 ) -> Result<BTreeMap<String, NorgMeta>, JanetConversionError> {
@@ -30,6 +32,7 @@ pub(crate) fn janetkv_to_metaobj(
     Ok(obj)
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<Janet> for NorgMeta {
     type Error = JanetConversionError;
 
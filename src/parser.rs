@@ -1,40 +1,97 @@
-use std::{collections::HashMap, hash::{DefaultHasher, Hash as _, Hasher as _}};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    block::{ListItem, NorgBlock},
-    inline::{Attribute, NorgInline},
+    block::{NorgBlock, Tag},
+    borrowed,
+    diagnostic::{Diagnostic, Severity},
+    inline::Attribute,
+    slug::Slugger,
+    target::NorgLinkTarget,
+};
+#[cfg(feature = "tree-sitter")]
+use crate::{
+    block::{Heading, ListItem, TableCell, TableRow, Todo},
+    inline::NorgInline,
+    slug::{markup_text, slugify},
 };
 
 // pub type Markup = String;
-pub type AnchorMap = HashMap<u64, AnchorDefinitionNode>;
+/// anchor definitions by their id
+pub type AnchorMap = HashMap<String, AnchorDefinitionNode>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct AnchorDefinitionNode {
-    /// byte range
     pub range: Range,
-    // TODO: change this to target::NorgLinkTarget when we start parsing target syntax from rust
-    // parser
-    pub target: String,
+    pub target: NorgLinkTarget,
+}
+
+/// Anchor definitions and references that don't line up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub enum AnchorIssue {
+    /// anchor defined more than once; `first` is the definition kept in [`AnchorMap`]
+    Duplicate {
+        id: String,
+        first: Range,
+        second: Range,
+    },
+    /// `[anchor]` reference without a definition anywhere in the document
+    Dangling { id: String, range: Range },
 }
 
+/// Version of the serialized [`NorgAST`], bumped whenever the serialized form of the AST
+/// changes incompatibly.
+pub const AST_VERSION: u32 = 1;
+
+/// Parsed norg document.
+///
+/// Serialized as an object with the fields below and a `version` set to [`AST_VERSION`], so a
+/// cached AST from another version fails to deserialize instead of being misread:
+///
+/// ```json
+/// {"version": 1, "blocks": [...], "anchors": {"id": {"range": ..., "target": ...}},
+///  "anchor_issues": [...], "diagnostics": [...]}
+/// ```
+///
+/// Blocks, inlines and other enums use serde's default externally tagged form, like
+/// `{"Paragraph": {"attrs": [], "inlines": [...], "range": ...}}` or `{"Key": "name"}` for
+/// [`Attribute::Key`]. Anchors are sorted by id. Only `version` and `blocks` are required when
+/// deserializing. [`crate::block::NorgBlock::Embed`] holds a janet function and can't be
+/// serialized; parsed ASTs never have it.
+///
+/// `S` is the type of source text, see [`borrowed::NorgAST`] for an AST of `&str` slices.
 #[derive(Debug)]
-pub struct NorgAST {
+pub struct NorgAST<S = String> {
     pub anchors: AnchorMap,
-    pub blocks: Vec<NorgBlock>,
+    /// ordered by position of the offending definition or reference
+    pub anchor_issues: Vec<AnchorIssue>,
+    pub blocks: Vec<NorgBlock<S>>,
+    /// problems found while parsing, ordered by position
+    pub diagnostics: Vec<Diagnostic>,
 }
 
+#[cfg(feature = "janet")]
 impl Into<janetrs::JanetStruct<'_>> for AnchorDefinitionNode {
     fn into(self) -> janetrs::JanetStruct<'static> {
         janetrs::JanetStruct::builder(2)
-            .put(
-                janetrs::JanetKeyword::new("target"),
-                janetrs::JanetString::from(self.target),
-            )
+            .put(janetrs::JanetKeyword::new("target"), self.target)
             .put(janetrs::JanetKeyword::new("range"), self.range)
             .finalize()
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<janetrs::JanetStruct<'_>> for NorgAST {
     fn into(self) -> janetrs::JanetStruct<'static> {
         janetrs::JanetStruct::builder(2)
@@ -44,9 +101,8 @@ impl Into<janetrs::JanetStruct<'_>> for NorgAST {
                     self.anchors
                         .into_iter()
                         .map(|(key, value)| {
-                            // let value = janetrs::JanetString::from(value);
                             let value: janetrs::JanetStruct = value.into();
-                            (key, value)
+                            (janetrs::JanetString::from(key), value)
                         })
                         .collect(),
                 ),
@@ -59,343 +115,1515 @@ impl Into<janetrs::JanetStruct<'_>> for NorgAST {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize)]
+struct SerializeAst<'a, B> {
+    version: u32,
+    blocks: &'a [B],
+    #[serde(serialize_with = "serialize_sorted")]
+    anchors: &'a AnchorMap,
+    anchor_issues: &'a [AnchorIssue],
+    diagnostics: &'a [Diagnostic],
+}
+
+impl<'a, B> SerializeAst<'a, B> {
+    fn new(
+        blocks: &'a [B],
+        anchors: &'a AnchorMap,
+        anchor_issues: &'a [AnchorIssue],
+        diagnostics: &'a [Diagnostic],
+    ) -> Self {
+        Self {
+            version: AST_VERSION,
+            blocks,
+            anchors,
+            anchor_issues,
+            diagnostics,
+        }
+    }
+}
+
+fn serialize_sorted<S: serde::Serializer>(
+    anchors: &&AnchorMap,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(anchors.iter().collect::<std::collections::BTreeMap<_, _>>())
+}
+
+impl<T: Serialize> Serialize for NorgAST<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializeAst::new(
+            &self.blocks,
+            &self.anchors,
+            &self.anchor_issues,
+            &self.diagnostics,
+        )
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize)]
+struct DeserializeAst {
+    // checked as soon as it is read, before the rest is misread in an older shape
+    #[serde(rename = "version", deserialize_with = "deserialize_version")]
+    _version: (),
+    blocks: Vec<NorgBlock>,
+    #[serde(default)]
+    anchors: AnchorMap,
+    #[serde(default)]
+    anchor_issues: Vec<AnchorIssue>,
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+fn deserialize_version<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<(), D::Error> {
+    let version = u32::deserialize(deserializer)?;
+    if version != AST_VERSION {
+        return Err(serde::de::Error::custom(format!(
+            "unsupported norg AST version {version}, expected {AST_VERSION}"
+        )));
+    }
+    Ok(())
+}
+
+impl<'de> Deserialize<'de> for NorgAST {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ast = DeserializeAst::deserialize(deserializer)?;
+        Ok(Self {
+            anchors: ast.anchors,
+            anchor_issues: ast.anchor_issues,
+            blocks: ast.blocks,
+            diagnostics: ast.diagnostics,
+        })
+    }
+}
+
+/// Source span of a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct Range {
+    /// start byte offset
     pub start: usize,
+    /// end byte offset (exclusive)
     pub end: usize,
+    pub start_point: Point,
+    pub end_point: Point,
+}
+
+/// Zero-based line and byte column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
+pub struct Point {
+    pub row: usize,
+    pub column: usize,
+}
+
+// NOTE: spans don't take part in node identity, so the same markup hashes equally wherever it
+// is written.
+impl Hash for Range {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl Range {
+    /// smallest span covering both `self` and `other`
+    pub fn join(self, other: Self) -> Self {
+        let (start, start_point) = if self.start <= other.start {
+            (self.start, self.start_point)
+        } else {
+            (other.start, other.start_point)
+        };
+        let (end, end_point) = if self.end >= other.end {
+            (self.end, self.end_point)
+        } else {
+            (other.end, other.end_point)
+        };
+        Self {
+            start,
+            end,
+            start_point,
+            end_point,
+        }
+    }
+
+    /// read `:range` field from node struct. Nodes created from janet (e.g. tag expansion
+    /// results) don't have one.
+    #[cfg(feature = "janet")]
+    pub(crate) fn from_janet_node(node: &janetrs::JanetStruct) -> Result<Self, janetrs::JanetConversionError> {
+        node.get_owned(janetrs::JanetKeyword::new(b"range"))
+            .map(Self::try_from)
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
 }
 
+#[cfg(feature = "tree-sitter")]
 impl From<tree_sitter::Range> for Range {
     fn from(value: tree_sitter::Range) -> Self {
         Self {
             start: value.start_byte,
             end: value.end_byte,
+            start_point: value.start_point.into(),
+            end_point: value.end_point.into(),
         }
     }
 }
 
+#[cfg(feature = "tree-sitter")]
+impl From<Range> for tree_sitter::Range {
+    fn from(value: Range) -> Self {
+        Self {
+            start_byte: value.start,
+            end_byte: value.end,
+            start_point: value.start_point.into(),
+            end_point: value.end_point.into(),
+        }
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+impl From<tree_sitter::Point> for Point {
+    fn from(value: tree_sitter::Point) -> Self {
+        Self {
+            row: value.row,
+            column: value.column,
+        }
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+impl From<Point> for tree_sitter::Point {
+    fn from(value: Point) -> Self {
+        Self {
+            row: value.row,
+            column: value.column,
+        }
+    }
+}
+
+#[cfg(feature = "janet")]
 impl Into<janetrs::Janet> for Range {
     fn into(self) -> janetrs::Janet {
-        janetrs::Janet::tuple(self.into())
+        janetrs::Janet::structs(self.into())
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<janetrs::JanetStruct<'_>> for Range {
+    fn into(self) -> janetrs::JanetStruct<'static> {
+        janetrs::JanetStruct::builder(4)
+            .put(janetrs::JanetKeyword::new(b"start"), self.start)
+            .put(janetrs::JanetKeyword::new(b"end"), self.end)
+            .put(janetrs::JanetKeyword::new(b"start-point"), self.start_point)
+            .put(janetrs::JanetKeyword::new(b"end-point"), self.end_point)
+            .finalize()
+    }
+}
+
+#[cfg(feature = "janet")]
+impl TryFrom<janetrs::Janet> for Range {
+    type Error = janetrs::JanetConversionError;
+
+    fn try_from(value: janetrs::Janet) -> Result<Self, Self::Error> {
+        let value = value.try_unwrap::<janetrs::JanetStruct>()?;
+        let get = |key: &[u8]| {
+            value
+                .get_owned(janetrs::JanetKeyword::new(key))
+                .ok_or(janetrs::JanetConversionError::Other)
+        };
+        Ok(Self {
+            start: get(b"start")?.try_unwrap::<u32>()? as usize,
+            end: get(b"end")?.try_unwrap::<u32>()? as usize,
+            start_point: get(b"start-point")?.try_into()?,
+            end_point: get(b"end-point")?.try_into()?,
+        })
+    }
+}
+
+#[cfg(feature = "janet")]
+impl Into<janetrs::Janet> for Point {
+    fn into(self) -> janetrs::Janet {
+        janetrs::Janet::tuple(janetrs::tuple![self.row, self.column])
+    }
+}
+
+#[cfg(feature = "janet")]
+impl TryFrom<janetrs::Janet> for Point {
+    type Error = janetrs::JanetConversionError;
+
+    fn try_from(value: janetrs::Janet) -> Result<Self, Self::Error> {
+        let value = value.try_unwrap::<janetrs::JanetTuple>()?;
+        let [row, column] = value.as_ref() else {
+            return Err(janetrs::JanetConversionError::Other);
+        };
+        Ok(Self {
+            row: row.try_unwrap::<u32>()? as usize,
+            column: column.try_unwrap::<u32>()? as usize,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// source text is not valid UTF-8
+    InvalidUtf8(std::str::Utf8Error),
+    /// tree-sitter-norg grammar couldn't be loaded
+    #[cfg(feature = "tree-sitter")]
+    LanguageError(tree_sitter::LanguageError),
+    /// tree-sitter stopped before producing a syntax tree
+    Aborted,
+    /// parsing took longer than [`NorgParser::set_timeout`]
+    TimedOut,
+    /// flag set with [`NorgParser::set_cancellation_flag`] was raised
+    Cancelled,
+    /// [`crate::document::TextEdit`] range is out of the text or not on char boundaries
+    InvalidEdit {
+        start_byte: usize,
+        old_end_byte: usize,
+    },
+    /// syntax tree doesn't have the shape this parser expects from the grammar
+    UnexpectedNode {
+        kind: String,
+        range: Range,
+        /// what the parser was looking for in the node
+        expected: &'static str,
+    },
+}
+
+impl ParseError {
+    #[cfg(feature = "tree-sitter")]
+    fn unexpected(node: tree_sitter::Node, expected: &'static str) -> Self {
+        Self::UnexpectedNode {
+            kind: node.kind().to_string(),
+            range: node.range().into(),
+            expected,
+        }
     }
 }
 
-impl Into<janetrs::JanetTuple<'_>> for Range {
-    fn into(self) -> janetrs::JanetTuple<'static> {
-        janetrs::tuple![self.start, self.end]
+impl From<std::str::Utf8Error> for ParseError {
+    fn from(value: std::str::Utf8Error) -> Self {
+        Self::InvalidUtf8(value)
     }
 }
 
+#[cfg(feature = "tree-sitter")]
+impl From<tree_sitter::LanguageError> for ParseError {
+    fn from(value: tree_sitter::LanguageError) -> Self {
+        Self::LanguageError(value)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUtf8(err) => write!(f, "invalid utf-8 in norg source: {err}"),
+            #[cfg(feature = "tree-sitter")]
+            Self::LanguageError(err) => write!(f, "error loading norg grammar: {err}"),
+            Self::Aborted => write!(f, "parsing was aborted"),
+            Self::TimedOut => write!(f, "parsing timed out"),
+            Self::Cancelled => write!(f, "parsing was cancelled"),
+            Self::InvalidEdit {
+                start_byte,
+                old_end_byte,
+            } => write!(f, "invalid edit of bytes {start_byte}..{old_end_byte}"),
+            Self::UnexpectedNode {
+                kind,
+                range,
+                expected,
+            } => write!(
+                f,
+                "unexpected `{kind}` node at {}:{}: expected {expected}",
+                range.start_point.row + 1,
+                range.start_point.column + 1,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse norg document.
+///
+/// # Panics
+///
+/// Panics when [`try_parse`] fails.
 pub fn parse(text: &[u8]) -> NorgAST {
-    let mut parser = tree_sitter::Parser::new();
-    let language = tree_sitter_norg::LANGUAGE;
-    parser
-        .set_language(&language.into())
-        .expect("Error loading Norg parser");
-    let tree = parser.parse(&text, None).unwrap();
-    parse_tstree(&tree, text)
+    try_parse(text).expect("failed to parse norg document")
+}
+
+/// Parse norg document with a one-off [`NorgParser`].
+pub fn try_parse(text: &[u8]) -> Result<NorgAST, ParseError> {
+    NorgParser::new()?.parse(text)
+}
+
+/// Parse norg document into AST borrowing its text from `text`.
+pub fn try_parse_borrowed(text: &[u8]) -> Result<borrowed::NorgAST<'_>, ParseError> {
+    NorgParser::new()?.parse_borrowed(text)
+}
+
+/// Norg parser with the grammar loaded once, to be reused across documents.
+pub struct NorgParser {
+    backend: Backend,
+    timeout: Option<Duration>,
+    cancellation_flag: Option<Arc<AtomicBool>>,
+}
+
+enum Backend {
+    #[cfg(feature = "tree-sitter")]
+    TreeSitter(tree_sitter::Parser),
+    PureRust,
+}
+
+impl NorgParser {
+    /// Parser with tree-sitter-norg, or with the pure-rust [`crate::parser2`] when built
+    /// without `tree-sitter` feature or with `pure-rust-parser` feature.
+    pub fn new() -> Result<Self, ParseError> {
+        #[cfg(all(feature = "tree-sitter", not(feature = "pure-rust-parser")))]
+        {
+            Self::tree_sitter()
+        }
+        #[cfg(any(not(feature = "tree-sitter"), feature = "pure-rust-parser"))]
+        {
+            Ok(Self::pure_rust())
+        }
+    }
+
+    /// Parser with tree-sitter-norg, also when `pure-rust-parser` feature is enabled.
+    #[cfg(feature = "tree-sitter")]
+    pub fn tree_sitter() -> Result<Self, ParseError> {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&tree_sitter_norg::LANGUAGE.into())?;
+        Ok(Self::with_backend(Backend::TreeSitter(parser)))
+    }
+
+    /// Parser with the pure-rust [`crate::parser2`], also when tree-sitter-norg is available.
+    /// Both give the same AST.
+    pub fn pure_rust() -> Self {
+        Self::with_backend(Backend::PureRust)
+    }
+
+    fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            timeout: None,
+            cancellation_flag: None,
+        }
+    }
+
+    /// Give up on a document once parsing it took longer than `timeout`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Stop parsing as soon as `flag` is set. The flag can be shared between parsers and
+    /// threads to cancel a whole batch.
+    pub fn set_cancellation_flag(&mut self, flag: Option<Arc<AtomicBool>>) {
+        self.cancellation_flag = flag;
+    }
+
+    pub fn parse(&mut self, text: &[u8]) -> Result<NorgAST, ParseError> {
+        self.parse_ast(text)
+    }
+
+    pub fn parse_borrowed<'src>(&mut self, text: &'src [u8]) -> Result<borrowed::NorgAST<'src>, ParseError> {
+        self.parse_ast(text)
+    }
+
+    fn parse_ast<'src, S: SourceText<'src>>(&mut self, text: &'src [u8]) -> Result<NorgAST<S>, ParseError> {
+        let stop = self.stop_check();
+        match &mut self.backend {
+            #[cfg(feature = "tree-sitter")]
+            Backend::TreeSitter(parser) => {
+                let tree = parse_tree(parser, &stop, text)?;
+                tstree_to_ast(&tree, text, stop)
+            }
+            Backend::PureRust => {
+                crate::parser2::parse_with_stop(std::str::from_utf8(text)?, &mut || stop.check())
+            }
+        }
+    }
+
+    /// Parse `texts` across threads, one parser per thread, all with the timeout and
+    /// cancellation flag of this parser. Results are in the order of `texts`.
+    ///
+    /// Fails only when the grammar can't be loaded for the thread parsers.
+    pub fn parse_many<T: AsRef<[u8]> + Sync>(
+        &self,
+        texts: &[T],
+    ) -> Result<Vec<Result<NorgAST, ParseError>>, ParseError> {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, usize::from)
+            .min(texts.len());
+        let parsers = (0..threads)
+            .map(|_| {
+                let mut parser = match self.backend {
+                    #[cfg(feature = "tree-sitter")]
+                    Backend::TreeSitter(_) => NorgParser::tree_sitter()?,
+                    Backend::PureRust => NorgParser::pure_rust(),
+                };
+                parser.set_timeout(self.timeout);
+                parser.set_cancellation_flag(self.cancellation_flag.clone());
+                Ok(parser)
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..texts.len()).map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for mut parser in parsers {
+                let (next, results) = (&next, &results);
+                scope.spawn(move || {
+                    // texts are handed out one by one so a few large files don't stall a thread
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(text) = texts.get(i) else {
+                            break;
+                        };
+                        let result = if parser.is_cancelled() {
+                            Err(ParseError::Cancelled)
+                        } else {
+                            parser.parse(text.as_ref())
+                        };
+                        results.lock().unwrap()[i] = Some(result);
+                    }
+                });
+            }
+        });
+        Ok(results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every text is parsed by some thread"))
+            .collect())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    fn stop_check(&self) -> StopCheck {
+        StopCheck {
+            start: Instant::now(),
+            timeout: self.timeout,
+            cancellation_flag: self.cancellation_flag.clone(),
+        }
+    }
 }
 
-pub fn parse_tstree(tree: &tree_sitter::Tree, text: &[u8]) -> NorgAST {
+#[cfg(feature = "tree-sitter")]
+fn parse_tree(
+    parser: &mut tree_sitter::Parser,
+    stop: &StopCheck,
+    text: &[u8],
+) -> Result<tree_sitter::Tree, ParseError> {
+    let mut stopped = None;
+    let mut progress = |_: &tree_sitter::ParseState| {
+        stopped = stop.check();
+        // returning true halts the parse
+        stopped.is_some()
+    };
+    let tree = parser.parse_with_options(
+        &mut |i, _| text.get(i..).unwrap_or_default(),
+        None,
+        Some(tree_sitter::ParseOptions::new().progress_callback(&mut progress)),
+    );
+    // parser keeps its state after a halt to resume from; next document starts fresh
+    parser.reset();
+    tree.ok_or(stopped.unwrap_or(ParseError::Aborted))
+}
+
+/// Timeout and cancellation of a single parse
+struct StopCheck {
+    start: Instant,
+    timeout: Option<Duration>,
+    cancellation_flag: Option<Arc<AtomicBool>>,
+}
+
+impl Default for StopCheck {
+    /// no timeout and no cancellation
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            timeout: None,
+            cancellation_flag: None,
+        }
+    }
+}
+
+impl StopCheck {
+    /// why parsing should stop now, if it should
+    fn check(&self) -> Option<ParseError> {
+        if self
+            .cancellation_flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+        {
+            Some(ParseError::Cancelled)
+        } else if self
+            .timeout
+            .is_some_and(|timeout| self.start.elapsed() >= timeout)
+        {
+            Some(ParseError::TimedOut)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+pub fn parse_tstree(tree: &tree_sitter::Tree, text: &[u8]) -> Result<NorgAST, ParseError> {
+    tstree_to_ast(tree, text, StopCheck::default())
+}
+
+#[cfg(feature = "tree-sitter")]
+pub fn parse_tstree_borrowed<'src>(
+    tree: &tree_sitter::Tree,
+    text: &'src [u8],
+) -> Result<borrowed::NorgAST<'src>, ParseError> {
+    tstree_to_ast(tree, text, StopCheck::default())
+}
+
+/// AST of a parsed tree. `stop` limits parsing of table cells, which are parsed on their own.
+#[cfg(feature = "tree-sitter")]
+fn tstree_to_ast<'src, S: SourceText<'src>>(
+    tree: &tree_sitter::Tree,
+    text: &'src [u8],
+    stop: StopCheck,
+) -> Result<NorgAST<S>, ParseError> {
+    std::str::from_utf8(text)?;
     let root = tree.root_node();
-    let mut anchors = HashMap::new();
-    let blocks = tsnode_to_blocks(&mut anchors, root, text);
-    NorgAST { anchors, blocks }
+    let mut state = ParseState {
+        stop,
+        ..ParseState::default()
+    };
+    report_syntax_errors(&mut state, root);
+    let blocks = tsnode_to_blocks(&mut state, root, text)?;
+    Ok(state.finish(blocks))
 }
 
+/// Text of the AST built by a parser: owned `String`s, or `&'src str` slices of the source for
+/// a [`borrowed`] AST
+pub(crate) trait SourceText<'src>: From<&'src str> + AsRef<str> {}
+
+impl<'src, S: From<&'src str> + AsRef<str>> SourceText<'src> for S {}
+
+/// State shared by both parsers while walking a document
 #[derive(Default)]
-struct CarryoverScanner {
-    tags: Vec<(String, Option<String>)>,
-    attrs: Vec<Attribute>,
+pub(crate) struct ParseState {
+    anchors: AnchorMap,
+    anchor_issues: Vec<AnchorIssue>,
+    /// ids and spans of `[anchor]` references without a target
+    anchor_refs: Vec<(String, Range)>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// heading ids given out so far
+    pub(crate) slugger: Slugger,
+    /// footnote ids given out so far. They're kept apart from heading ids, as exporters prefix
+    /// them anyway
+    pub(crate) footnote_slugger: Slugger,
+    /// timeout and cancellation of the running parse, also applied to table cells
+    #[cfg(feature = "tree-sitter")]
+    stop: StopCheck,
+}
+
+impl ParseState {
+    /// Record anchor `id` found at `range`, either a definition with `target` or a reference.
+    pub(crate) fn add_anchor(&mut self, id: &str, target: Option<&NorgLinkTarget>, range: Range) {
+        match (target, self.anchors.get(id)) {
+            (Some(_), Some(first)) => self.anchor_issues.push(AnchorIssue::Duplicate {
+                id: id.to_string(),
+                first: first.range,
+                second: range,
+            }),
+            (Some(target), None) => {
+                self.anchors.insert(
+                    id.to_string(),
+                    AnchorDefinitionNode {
+                        range,
+                        target: target.clone(),
+                    },
+                );
+            }
+            (None, _) => self.anchor_refs.push((id.to_string(), range)),
+        }
+    }
+
+    pub(crate) fn finish<S>(mut self, blocks: Vec<NorgBlock<S>>) -> NorgAST<S> {
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+        // references may come before their definition, so they are only checked at the end
+        let dangling = std::mem::take(&mut self.anchor_refs)
+            .into_iter()
+            .filter(|(id, _)| !self.anchors.contains_key(id))
+            .map(|(id, range)| AnchorIssue::Dangling { id, range });
+        self.anchor_issues.extend(dangling);
+        self.anchor_issues.sort_by_key(|issue| match issue {
+            AnchorIssue::Duplicate { second, .. } => second.start,
+            AnchorIssue::Dangling { range, .. } => range.start,
+        });
+        NorgAST {
+            anchors: self.anchors,
+            anchor_issues: self.anchor_issues,
+            blocks,
+            diagnostics: self.diagnostics,
+        }
+    }
+}
+
+/// Carryover attributes and tags waiting for the next block
+pub(crate) struct CarryoverScanner<S> {
+    pub(crate) tags: Vec<Tag<S>>,
+    pub(crate) attrs: Vec<Attribute>,
 }
 
-fn tsnode_to_blocks(anchors: &mut AnchorMap, node: tree_sitter::Node, text: &[u8]) -> Vec<NorgBlock> {
+impl<S> Default for CarryoverScanner<S> {
+    fn default() -> Self {
+        Self {
+            tags: vec![],
+            attrs: vec![],
+        }
+    }
+}
+
+impl<S: AsRef<str>> CarryoverScanner<S> {
+    /// Collect `node` if it is a carryover attribute or tag. Returns whether it was consumed.
+    #[cfg(feature = "tree-sitter")]
+    fn scan<'src>(&mut self, node: tree_sitter::Node, text: &'src [u8]) -> Result<bool, ParseError>
+    where
+        S: From<&'src str>,
+    {
+        match node.kind() {
+            "carryover_attributes" => {
+                let attrs = get_attributes_from_tsnode(node, text)?
+                    .ok_or_else(|| ParseError::unexpected(node, "attributes"))?;
+                self.attrs.extend(attrs);
+            }
+            "carryover_tag" => self.tags.push(Tag {
+                name: node_text(field(node, "name")?, text)?.into(),
+                params: tag_params(node, text)?,
+                range: node.range().into(),
+            }),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Wrap `block` with the collected carryover tags, first tag being the innermost.
+    pub(crate) fn apply_tags(&mut self, block: NorgBlock<S>) -> NorgBlock<S> {
+        std::mem::take(&mut self.tags)
+            .into_iter()
+            .fold(block, |block, tag| NorgBlock::CarryoverTag {
+                name: tag.name,
+                params: tag.params,
+                range: tag.range.join(block.range()),
+                target: Box::new(block),
+            })
+    }
+
+    /// Report carryover tags left without a block to apply to.
+    pub(crate) fn finish(self, state: &mut ParseState) {
+        for tag in self.tags {
+            state.diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                message: format!(
+                    "carryover tag `#{}` has no block to apply to and was dropped",
+                    tag.name.as_ref()
+                ),
+                range: tag.range,
+                kind: String::from("carryover_tag"),
+                dropped: true,
+            });
+        }
+    }
+}
+
+/// Report every ERROR and MISSING node in the tree.
+#[cfg(feature = "tree-sitter")]
+fn report_syntax_errors(state: &mut ParseState, node: tree_sitter::Node) {
+    if node.is_error() {
+        // error node covers all of its children
+        state.diagnostics.push(Diagnostic {
+            dropped: true,
+            ..Diagnostic::error("unexpected syntax", node)
+        });
+    } else if node.is_missing() {
+        state
+            .diagnostics
+            .push(Diagnostic::error(format!("missing `{}`", node.kind()), node));
+    } else if node.has_error() {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            report_syntax_errors(state, child);
+        }
+    }
+}
+
+/// Report named node that didn't make it to the AST.
+/// Field children (`heading`, `attributes`, ...) and prefixes are consumed by their parent and
+/// syntax errors are already reported by [`report_syntax_errors`].
+#[cfg(feature = "tree-sitter")]
+fn report_dropped(state: &mut ParseState, parent: tree_sitter::Node, index: usize, node: tree_sitter::Node) {
+    if node.is_error()
+        || node.is_missing()
+        || node.kind().ends_with("prefix")
+        || parent.field_name_for_named_child(index as u32).is_some()
+    {
+        return;
+    }
+    state.diagnostics.push(Diagnostic {
+        dropped: true,
+        ..Diagnostic::warning(
+            format!("`{}` is not supported yet and was dropped", node.kind()),
+            node,
+        )
+    });
+}
+
+#[cfg(feature = "tree-sitter")]
+fn field<'tree>(node: tree_sitter::Node<'tree>, name: &'static str) -> Result<tree_sitter::Node<'tree>, ParseError> {
+    node.child_by_field_name(name)
+        .ok_or_else(|| ParseError::unexpected(node, name))
+}
+
+#[cfg(feature = "tree-sitter")]
+fn node_text<'a>(node: tree_sitter::Node, text: &'a [u8]) -> Result<&'a str, ParseError> {
+    Ok(node.utf8_text(text)?)
+}
+
+/// Parameters of the tag `node`, covering everything from its first `param` to the last one.
+#[cfg(feature = "tree-sitter")]
+fn tag_params(node: tree_sitter::Node, text: &[u8]) -> Result<Vec<String>, ParseError> {
     let mut cursor = node.walk();
+    let params: Vec<_> = node.children_by_field_name("param", &mut cursor).collect();
+    match (params.first(), params.last()) {
+        (Some(first), Some(last)) => {
+            let raw = std::str::from_utf8(&text[first.start_byte()..last.end_byte()])?;
+            Ok(parse_tag_params(raw))
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+/// Split raw tag parameters on whitespace.
+///
+/// Parameters can be quoted with `"` to keep whitespace in them, and `\` escapes the next
+/// character either way: `@code "my lang" a\ b` has parameters `my lang` and `a b`.
+pub(crate) fn parse_tag_params(raw: &str) -> Vec<String> {
+    let mut params = vec![];
+    let mut param: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // trailing backslash is kept as is
+            '\\' => param
+                .get_or_insert_with(String::new)
+                .push(chars.next().unwrap_or('\\')),
+            '"' => {
+                quoted = !quoted;
+                // `""` is an empty parameter
+                param.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => params.extend(param.take()),
+            c => param.get_or_insert_with(String::new).push(c),
+        }
+    }
+    params.extend(param);
+    params
+}
+
+/// length of the detached modifier prefix (`***`, `---`, ...) of a heading or list item
+#[cfg(feature = "tree-sitter")]
+fn prefix_len(node: tree_sitter::Node, text: &[u8]) -> Result<u16, ParseError> {
+    let prefix = node
+        .child(0)
+        .ok_or_else(|| ParseError::unexpected(node, "prefix"))?;
+    Ok(node_text(prefix, text)?.len() as u16)
+}
+
+#[cfg(feature = "tree-sitter")]
+fn tsnode_to_blocks<'src, S: SourceText<'src>>(state: &mut ParseState, parent: tree_sitter::Node, text: &'src [u8]) -> Result<Vec<NorgBlock<S>>, ParseError> {
+    let mut cursor = parent.walk();
     let mut carryovers = CarryoverScanner::default();
-    node.named_children(&mut cursor)
-        .flat_map(|node| {
-            let block = match node.kind() {
-                "section" => {
-                    let heading_node = node.child_by_field_name("heading").unwrap();
-                    let prefix_count = heading_node
-                        .child(0)
-                        .expect("heading node should have at least one child")
-                        .utf8_text(text)
-                        .expect("heading prefix should be valid utf8 text")
-                        .len();
-                    let title = heading_node
-                        .child_by_field_name("title")
-                        .map(|node| tsnode_to_inlines(anchors, node, text));
-                    let attrs = get_attributes_from_tsnode(heading_node, text).unwrap_or(vec![]);
-                    Some(NorgBlock::Section {
-                        attrs,
-                        level: prefix_count as u16,
-                        heading: title,
-                        contents: tsnode_to_blocks(anchors, node, text),
-                    })
-                }
-                "paragraph" => Some(NorgBlock::Paragraph {
+    let mut blocks = vec![];
+    for (index, node) in parent.named_children(&mut cursor).enumerate() {
+        // title of definitions and footnotes isn't part of their contents
+        if parent.field_name_for_named_child(index as u32) == Some("title") {
+            continue;
+        }
+        if carryovers.scan(node, text)? {
+            continue;
+        }
+        let range: Range = node.range().into();
+        let block = match node.kind() {
+            "section" => {
+                let heading_node = field(node, "heading")?;
+                let inlines = heading_node
+                    .child_by_field_name("title")
+                    .map(|node| tsnode_to_inlines(state, node, text))
+                    .transpose()?
+                    .unwrap_or(vec![]);
+                let attrs = get_attributes_from_tsnode(heading_node, text)?.unwrap_or(vec![]);
+                let heading = Heading {
+                    todo: Todo::from_attrs(&attrs),
+                    id: state.slugger.unique(&markup_text(&inlines)),
+                    attrs,
+                    inlines,
+                    range: heading_node.range().into(),
+                };
+                NorgBlock::Section {
                     attrs: std::mem::take(&mut carryovers.attrs),
-                    inlines: tsnode_to_inlines(anchors, node, text),
-                }),
-                "infirm_tag" => {
-                    let name = node
-                        .child_by_field_name("name")
-                        .unwrap()
-                        .utf8_text(text)
-                        .unwrap()
-                        .to_string();
-                    let raw_param = node
-                        .child_by_field_name("param")
-                        .map(|node| node.utf8_text(text).unwrap().to_string());
-                    Some(NorgBlock::InfirmTag {
-                        name,
-                        params: raw_param,
-                    })
+                    level: prefix_len(heading_node, text)?,
+                    heading: Some(heading),
+                    contents: tsnode_to_blocks(state, node, text)?,
+                    range,
                 }
-                "ranged_tag" => {
-                    let name = node
-                        .child_by_field_name("name")
-                        .unwrap()
-                        .utf8_text(text)
-                        .unwrap()
-                        .to_string();
-                    let raw_param = node
-                        .child_by_field_name("param")
-                        .map(|node| node.utf8_text(text).unwrap().to_string());
-                    let mut cursor = node.walk();
-                    let lines = node
-                        .children_by_field_name("line", &mut cursor)
-                        .map(|node| node.utf8_text(text).unwrap().to_string())
-                        .collect();
-                    Some(NorgBlock::RangedTag {
-                        name,
-                        params: raw_param,
-                        content: lines,
-                    })
-                }
-                "carryover_attributes" => {
-                    let attrs = get_attributes_from_tsnode(node, text).unwrap();
-                    carryovers.attrs.extend(attrs);
-                    None
-                }
-                "carryover_tag" => {
-                    let name = node
-                        .child_by_field_name("name")
-                        .unwrap()
-                        .utf8_text(text)
-                        .unwrap()
-                        .to_string();
-                    let raw_param = node
-                        .child_by_field_name("param")
-                        .map(|node| node.utf8_text(text).unwrap().to_string());
-                    carryovers.tags.push((name, raw_param));
-                    None
+            }
+            "paragraph" => NorgBlock::Paragraph {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                inlines: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "infirm_tag" => {
+                let name = node_text(field(node, "name")?, text)?;
+                let params = tag_params(node, text)?;
+                NorgBlock::InfirmTag {
+                    attrs: std::mem::take(&mut carryovers.attrs),
+                    name: name.into(),
+                    params,
+                    range,
                 }
-                "unordered_list" => {
-                    let prefix_node = node.child(0).unwrap().child(0).unwrap();
-                    let prefix_count = prefix_node.utf8_text(text).unwrap().len();
-                    Some(NorgBlock::UnorderedList {
-                        // TODO: this way won't work with tags
-                        attrs: std::mem::take(&mut carryovers.attrs),
-                        level: prefix_count as u16,
-                        items: {
-                            let mut cursor = node.walk();
-                            node.named_children(&mut cursor)
-                                .map(|node| ListItem {
-                                    attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                                    contents: tsnode_to_blocks(anchors, node, text),
-                                })
-                                .collect()
-                        },
-                    })
+            }
+            "ranged_tag" => {
+                let name = node_text(field(node, "name")?, text)?;
+                let params = tag_params(node, text)?;
+                let mut cursor = node.walk();
+                let lines = node
+                    .children_by_field_name("line", &mut cursor)
+                    .map(|node| node_text(node, text).map(S::from))
+                    .collect::<Result<_, _>>()?;
+                if !node.has_error() && !node_text(node, text)?.trim_end().ends_with("@end") {
+                    state.diagnostics.push(Diagnostic::error(
+                        format!("ranged tag `@{name}` is not closed with `@end`"),
+                        node,
+                    ));
                 }
-                "ordered_list" => {
-                    let prefix_node = node.child(0).unwrap().child(0).unwrap();
-                    let prefix_count = prefix_node.utf8_text(text).unwrap().len();
-                    Some(NorgBlock::OrderedList {
-                        attrs: std::mem::take(&mut carryovers.attrs),
-                        level: prefix_count as u16,
-                        items: {
-                            let mut cursor = node.walk();
-                            node.named_children(&mut cursor)
-                                .map(|node| ListItem {
-                                    attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                                    contents: tsnode_to_blocks(anchors, node, text),
-                                })
-                                .collect()
+                match name {
+                    "table" => match tsnode_to_table_rows(state, node, text)? {
+                        Some(rows) => NorgBlock::Table {
+                            attrs: std::mem::take(&mut carryovers.attrs),
+                            rows,
+                            range,
                         },
-                    })
-                }
-                "quote" => {
-                    let prefix_node = node.child(0).unwrap().child(0).unwrap();
-                    let prefix_count = prefix_node.utf8_text(text).unwrap().len();
-                    Some(NorgBlock::Quote {
+                        None => {
+                            state.diagnostics.push(Diagnostic::warning(
+                                "`@table` content is not a pipe-delimited table",
+                                node,
+                            ));
+                            NorgBlock::RangedTag {
+                                attrs: std::mem::take(&mut carryovers.attrs),
+                                name: name.into(),
+                                params,
+                                content: lines,
+                                range,
+                            }
+                        }
+                    },
+                    _ => NorgBlock::RangedTag {
                         attrs: std::mem::take(&mut carryovers.attrs),
-                        level: prefix_count as u16,
-                        items: {
-                            let mut cursor = node.walk();
-                            node.named_children(&mut cursor)
-                                .map(|node| ListItem {
-                                    attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                                    contents: tsnode_to_blocks(anchors, node, text),
-                                })
-                                .collect()
-                        },
-                    })
+                        name: name.into(),
+                        params,
+                        content: lines,
+                        range,
+                    },
                 }
-                "horizontal_line" => Some(NorgBlock::HorizontalLine { attrs: vec![] }),
-                _ => None,
-            };
-            block.map(|block| {
-                if carryovers.tags.len() > 0 {
-                    let tags = std::mem::take(&mut carryovers.tags);
-                    tags.into_iter()
-                        .fold(block, |block, (name, params)| NorgBlock::CarryoverTag {
-                            name,
-                            params,
-                            target: Box::new(block),
-                        })
-                } else {
-                    block
+            }
+            "unordered_list" => NorgBlock::UnorderedList {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                level: list_level(node, text)?,
+                items: tsnode_to_list_items(state, node, text)?,
+                range,
+            },
+            "ordered_list" => NorgBlock::OrderedList {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                level: list_level(node, text)?,
+                items: tsnode_to_list_items(state, node, text)?,
+                range,
+            },
+            "quote" => NorgBlock::Quote {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                level: list_level(node, text)?,
+                items: tsnode_to_list_items(state, node, text)?,
+                range,
+            },
+            "horizontal_line" => NorgBlock::HorizontalLine {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                range,
+            },
+            "single_definition" | "ranged_definition" => NorgBlock::Definition {
+                attrs: std::mem::take(&mut carryovers.attrs),
+                title: tsnode_to_inlines(state, field(node, "title")?, text)?,
+                contents: tsnode_to_blocks(state, node, text)?,
+                range,
+            },
+            "single_footnote" | "ranged_footnote" => {
+                let title = tsnode_to_inlines(state, field(node, "title")?, text)?;
+                NorgBlock::Footnote {
+                    attrs: std::mem::take(&mut carryovers.attrs),
+                    id: state.footnote_slugger.unique(&markup_text(&title)),
+                    title,
+                    contents: tsnode_to_blocks(state, node, text)?,
+                    range,
                 }
-            })
-        })
-        .collect()
+            }
+            _ => {
+                report_dropped(state, parent, index, node);
+                continue;
+            }
+        };
+        blocks.push(carryovers.apply_tags(block));
+    }
+    carryovers.finish(state);
+    Ok(blocks)
 }
 
-fn tsnode_to_inlines(anchors: &mut AnchorMap, node: tree_sitter::Node, text: &[u8]) -> Vec<NorgInline> {
+/// nesting level of the list, taken from the prefix of its first item
+#[cfg(feature = "tree-sitter")]
+fn list_level(node: tree_sitter::Node, text: &[u8]) -> Result<u16, ParseError> {
     let mut cursor = node.walk();
-    use NorgInline::*;
-    node.named_children(&mut cursor)
-        .map(|node| match node.kind() {
-            "whitespace" => Some(Whitespace),
-            "soft_break" => Some(SoftBreak),
-            "hard_break" => Some(HardBreak),
-            "word" => {
-                let text = node.utf8_text(text).unwrap().to_string();
-                Some(Text(text))
+    let first_item = node
+        .named_children(&mut cursor)
+        .find(|child| !child.kind().starts_with("carryover_"))
+        .ok_or_else(|| ParseError::unexpected(node, "list item"))?;
+    prefix_len(first_item, text)
+}
+
+/// Rows of `@table` ranged tag with pipe-delimited lines:
+///
+/// ```norg
+/// @table
+/// | Name | (html.class num) Age |
+/// |------|----------------------|
+/// | Bob  | 42                   |
+/// @end
+/// ```
+///
+/// Rows above the delimiter row are header rows. Returns `None` when a line isn't a table row.
+#[cfg(feature = "tree-sitter")]
+fn tsnode_to_table_rows<'src, S: SourceText<'src>>(state: &mut ParseState, node: tree_sitter::Node, text: &'src [u8]) -> Result<Option<Vec<TableRow<S>>>, ParseError> {
+    thread_local! {
+        // cell contents are parsed on their own, so the grammar is loaded once per thread
+        static PARSER: std::cell::RefCell<Option<tree_sitter::Parser>> =
+            const { std::cell::RefCell::new(None) };
+    }
+    PARSER.with_borrow_mut(|parser| {
+        let parser = match parser {
+            Some(parser) => parser,
+            None => {
+                let mut new = tree_sitter::Parser::new();
+                new.set_language(&tree_sitter_norg::LANGUAGE.into())?;
+                parser.insert(new)
             }
-            "punctuation" => {
-                let text = node.utf8_text(text).unwrap().to_string();
-                Some(Special(text))
+        };
+        table_rows(state, parser, node, text)
+    })
+}
+
+#[cfg(feature = "tree-sitter")]
+fn table_rows<'src, S: SourceText<'src>>(
+    state: &mut ParseState,
+    parser: &mut tree_sitter::Parser,
+    node: tree_sitter::Node,
+    text: &'src [u8],
+) -> Result<Option<Vec<TableRow<S>>>, ParseError> {
+    let mut cursor = node.walk();
+    let mut rows = vec![];
+    let mut header_end = None;
+    for line in node.children_by_field_name("line", &mut cursor) {
+        let line_text = node_text(line, text)?;
+        if line_text.trim().is_empty() {
+            continue;
+        }
+        let Some(cells) = split_table_row(line_text) else {
+            return Ok(None);
+        };
+        if cells.iter().all(|&(start, end)| is_delimiter_cell(&line_text[start..end])) {
+            header_end.get_or_insert(rows.len());
+            continue;
+        }
+        let cells = cells
+            .into_iter()
+            .map(|(start, end)| table_cell(state, parser, line, start, end, text))
+            .collect::<Result<_, _>>()?;
+        rows.push(TableRow {
+            header: false,
+            cells,
+            range: line.range().into(),
+        });
+    }
+    for row in rows.iter_mut().take(header_end.unwrap_or(0)) {
+        row.header = true;
+    }
+    Ok(Some(rows))
+}
+
+/// byte ranges of cell contents in a `| a | b |` line. Trailing pipe is optional and `\|` doesn't
+/// separate cells.
+pub(crate) fn split_table_row(line: &str) -> Option<Vec<(usize, usize)>> {
+    let line = line.trim_end();
+    let start = line.len() - line.trim_start().len();
+    if !line[start..].starts_with('|') {
+        return None;
+    }
+    let mut cells = vec![];
+    let mut cell_start = start + 1;
+    let mut escaped = false;
+    for (i, c) in line.char_indices().skip_while(|&(i, _)| i <= start) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '|' => {
+                cells.push((cell_start, i));
+                cell_start = i + 1;
             }
-            "escape_sequence" => {
-                let character = node.utf8_text(text).unwrap().chars().nth(1).unwrap();
-                Some(Escape(character))
+            _ => {}
+        }
+    }
+    if cell_start < line.len() {
+        cells.push((cell_start, line.len()));
+    }
+    Some(cells)
+}
+
+/// `---`, `:--`, `:-:` or `--:`
+pub(crate) fn is_delimiter_cell(cell: &str) -> bool {
+    let cell = cell.trim();
+    let cell = cell.strip_prefix(':').unwrap_or(cell);
+    let cell = cell.strip_suffix(':').unwrap_or(cell);
+    !cell.is_empty() && cell.chars().all(|c| c == '-')
+}
+
+/// Table cell from `start..end` bytes of `line`. Cell can start with `(attrs)` and the rest is
+/// parsed as inline markup in place, so ranges point to the original text.
+#[cfg(feature = "tree-sitter")]
+fn table_cell<'src, S: SourceText<'src>>(
+    state: &mut ParseState,
+    parser: &mut tree_sitter::Parser,
+    line: tree_sitter::Node,
+    start: usize,
+    end: usize,
+    text: &'src [u8],
+) -> Result<TableCell<S>, ParseError> {
+    let cell = &node_text(line, text)?[start..end];
+    let mut offset = start + (cell.len() - cell.trim_start().len());
+    let mut content = cell.trim();
+    let mut attrs = vec![];
+    if let Some((attrs_text, rest)) = content
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+    {
+        attrs = parse_attribute_list(attrs_text);
+        let rest_trimmed = rest.trim_start();
+        offset += attrs_text.len() + 2 + (rest.len() - rest_trimmed.len());
+        content = rest_trimmed;
+    }
+    let mut inlines = vec![];
+    if !content.is_empty() {
+        parser
+            .set_included_ranges(&[line_range(line, offset, offset + content.len()).into()])
+            .map_err(|_| ParseError::unexpected(line, "table cell"))?;
+        let tree = parse_tree(parser, &state.stop, text)?;
+        let root = tree.root_node();
+        report_syntax_errors(state, root);
+        let mut cursor = root.walk();
+        for paragraph in root.named_children(&mut cursor) {
+            if paragraph.kind() == "paragraph" {
+                inlines.extend(tsnode_to_inlines(state, paragraph, text)?);
             }
+        }
+    }
+    Ok(TableCell {
+        attrs,
+        inlines,
+        range: line_range(line, start, end),
+    })
+}
+
+/// span of `start..end` bytes of single line node
+#[cfg(feature = "tree-sitter")]
+fn line_range(line: tree_sitter::Node, start: usize, end: usize) -> Range {
+    let line_start = line.start_position();
+    Range {
+        start: line.start_byte() + start,
+        end: line.start_byte() + end,
+        start_point: Point {
+            row: line_start.row,
+            column: line_start.column + start,
+        },
+        end_point: Point {
+            row: line_start.row,
+            column: line_start.column + end,
+        },
+    }
+}
+
+/// `key value; key; ...` attributes written as plain text
+pub(crate) fn parse_attribute_list(text: &str) -> Vec<Attribute> {
+    text.split(';')
+        .map(str::trim)
+        .map(|attr| match attr.split_once(char::is_whitespace) {
+            _ if attr.is_empty() => Attribute::Blank,
+            Some((key, value)) => Attribute::KeyValue(key.to_string(), value.trim().to_string()),
+            None => Attribute::Key(attr.to_string()),
+        })
+        .collect()
+}
+
+#[cfg(feature = "tree-sitter")]
+fn tsnode_to_list_items<'src, S: SourceText<'src>>(state: &mut ParseState, node: tree_sitter::Node, text: &'src [u8]) -> Result<Vec<ListItem<S>>, ParseError> {
+    let mut cursor = node.walk();
+    let mut carryovers = CarryoverScanner::default();
+    let mut items = vec![];
+    for node in node.named_children(&mut cursor) {
+        if carryovers.scan(node, text)? {
+            continue;
+        }
+        let item_attrs = get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]);
+        let todo = Todo::from_attrs(&item_attrs);
+        let mut attrs = std::mem::take(&mut carryovers.attrs);
+        attrs.extend(item_attrs);
+        items.push(ListItem {
+            attrs,
+            todo,
+            tags: std::mem::take(&mut carryovers.tags),
+            contents: tsnode_to_blocks(state, node, text)?,
+            range: node.range().into(),
+        });
+    }
+    carryovers.finish(state);
+    Ok(items)
+}
+
+#[cfg(feature = "tree-sitter")]
+fn tsnode_to_inlines<'src, S: SourceText<'src>>(state: &mut ParseState, parent: tree_sitter::Node, text: &'src [u8]) -> Result<Vec<NorgInline<S>>, ParseError> {
+    let mut cursor = parent.walk();
+    use NorgInline::*;
+    let mut inlines = vec![];
+    for (index, node) in parent.named_children(&mut cursor).enumerate() {
+        let range: Range = node.range().into();
+        let inline = match node.kind() {
+            "whitespace" => Whitespace { range },
+            "soft_break" => SoftBreak { range },
+            "hard_break" => HardBreak { range },
+            "word" => Text {
+                text: node_text(node, text)?.into(),
+                range,
+            },
+            "punctuation" => Special {
+                text: node_text(node, text)?.into(),
+                range,
+            },
+            "escape_sequence" => Escape {
+                character: node_text(node, text)?
+                    .chars()
+                    .nth(1)
+                    .ok_or_else(|| ParseError::unexpected(node, "escaped character"))?,
+                range,
+            },
             // TODO: add attributes
-            "bold" => Some(Bold {
-                attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                markup: tsnode_to_inlines(anchors, node, text),
-            }),
-            "italic" => Some(Italic {
-                attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                markup: tsnode_to_inlines(anchors, node, text),
-            }),
-            "underline" => Some(Underline {
-                attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                markup: tsnode_to_inlines(anchors, node, text),
-            }),
-            "strikethrough" => Some(Strikethrough {
-                attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                markup: tsnode_to_inlines(anchors, node, text),
-            }),
-            "verbatim" => Some(Verbatim {
-                attrs: get_attributes_from_tsnode(node, text).unwrap_or(vec![]),
-                markup: tsnode_to_inlines(anchors, node, text),
-            }),
+            "bold" => Bold {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "italic" => Italic {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "underline" => Underline {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "strikethrough" => Strikethrough {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "verbatim" => Verbatim {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: verbatim_markup(state, node, text)?,
+                range,
+            },
+            "spoiler" => Spoiler {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "superscript" => Superscript {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "subscript" => Subscript {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
+            "inline_math" => InlineMath {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: verbatim_markup(state, node, text)?,
+                range,
+            },
+            "variable" => Variable {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: verbatim_markup(state, node, text)?,
+                range,
+            },
+            "null_modifier" | "inline_comment" => NullModifier {
+                attrs: get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]),
+                free_form: is_free_form(node, text)?,
+                markup: tsnode_to_inlines(state, node, text)?,
+                range,
+            },
             "inline_macro" => {
-                let name = node
-                    .child_by_field_name("name")
-                    .unwrap()
-                    .utf8_text(text)
-                    .unwrap()
-                    .to_string();
-                let attrs: Option<Vec<String>> =
-                    node.child_by_field_name("attributes").map(|attrs| {
+                let name = node_text(field(node, "name")?, text)?.into();
+                let attrs = node
+                    .child_by_field_name("attributes")
+                    .map(|attrs| {
                         let mut cursor = attrs.walk();
                         attrs
                             .named_children(&mut cursor)
-                            .map(|attr| attr.utf8_text(text).unwrap().to_string())
+                            .map(|attr| node_text(attr, text).map(S::from))
                             .collect()
-                    });
+                    })
+                    .transpose()?;
                 let markup = node
                     .child_by_field_name("markup")
-                    .map(|node| tsnode_to_inlines(anchors, node, text));
-                Some(Macro { name, markup, attrs })
+                    .map(|node| tsnode_to_inlines(state, node, text))
+                    .transpose()?;
+                Macro {
+                    name,
+                    markup,
+                    attrs,
+                    range,
+                }
             }
             "link" => {
-                let target = node
-                    .child_by_field_name("target")
-                    .unwrap()
-                    .utf8_text(text)
-                    .unwrap()
-                    .to_string();
+                let target = NorgLinkTarget::parse(node_text(field(node, "target")?, text)?);
                 let markup = node
                     .child_by_field_name("markup")
-                    .map(|node| tsnode_to_inlines(anchors, node, text));
-                let attrs = get_attributes_from_tsnode(node, text).unwrap_or(vec![]);
-                Some(Link {
+                    .map(|node| tsnode_to_inlines(state, node, text))
+                    .transpose()?;
+                let attrs = get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]);
+                Link {
                     target,
                     markup,
                     attrs,
-                })
+                    range,
+                }
             }
             "anchor" => {
                 let target = node
                     .child_by_field_name("target")
-                    .map(|node| node.utf8_text(text).unwrap().to_string());
-                let range: Range = node.range().into();
-                let markup_node = node.child_by_field_name("markup").unwrap();
-                let markup = tsnode_to_inlines(anchors, markup_node, text);
-                let hash = {
-                    let mut hasher = DefaultHasher::new();
-                    markup.hash(&mut hasher);
-                    hasher.finish()
-                };
-                if let Some(ref target) = target {
-                    anchors.insert(
-                        hash,
-                        AnchorDefinitionNode {
-                            range,
-                            target: target.clone(),
-                        },
-                    );
-                }
-                let attrs = get_attributes_from_tsnode(node, text).unwrap_or(vec![]);
-                Some(Anchor {
+                    .map(|node| node_text(node, text).map(NorgLinkTarget::parse))
+                    .transpose()?;
+                let markup = tsnode_to_inlines(state, field(node, "markup")?, text)?;
+                let id = slugify(&markup_text(&markup));
+                state.add_anchor(&id, target.as_ref(), range);
+                let attrs = get_attributes_from_tsnode(node, text)?.unwrap_or(vec![]);
+                Anchor {
                     target,
-                    hash,
+                    id,
                     markup,
                     attrs,
-                })
+                    range,
+                }
             }
-            _ => None,
+            _ => {
+                report_dropped(state, parent, index, node);
+                continue;
+            }
+        };
+        inlines.push(inline);
+    }
+    Ok(inlines)
+}
+
+/// attached modifier opened with `X|`
+#[cfg(feature = "tree-sitter")]
+fn is_free_form(node: tree_sitter::Node, text: &[u8]) -> Result<bool, ParseError> {
+    Ok(node_text(node, text)?.as_bytes().get(1) == Some(&b'|'))
+}
+
+/// Content of verbatim-like modifier. Free-form content between `X|` and `|X` is kept as raw
+/// text, as it can contain anything but the closing modifier.
+#[cfg(feature = "tree-sitter")]
+fn verbatim_markup<'src, S: SourceText<'src>>(state: &mut ParseState, node: tree_sitter::Node, text: &'src [u8]) -> Result<Vec<NorgInline<S>>, ParseError> {
+    if !is_free_form(node, text)? {
+        return tsnode_to_inlines(state, node, text);
+    }
+    let source = node_text(node, text)?;
+    let modifier = &source[..1];
+    // attributes come after the closing modifier
+    let end = node
+        .child_by_field_name("attributes")
+        .map_or(node.end_byte(), |attrs| attrs.start_byte())
+        - node.start_byte();
+    let closing = format!("|{modifier}");
+    let content_end = match source[..end].trim_end().strip_suffix(closing.as_str()) {
+        Some(content) => content.len(),
+        // unclosed modifier is reported as syntax error
+        None => end,
+    };
+    if content_end <= 2 {
+        return Ok(vec![]);
+    }
+    let range = Range {
+        start: node.start_byte() + 2,
+        end: node.start_byte() + content_end,
+        start_point: point_after(node.start_position().into(), &source[..2]),
+        end_point: point_after(node.start_position().into(), &source[..content_end]),
+    };
+    Ok(vec![NorgInline::Text {
+        text: source[2..content_end].into(),
+        range,
+    }])
+}
+
+/// position at the end of `text` starting at `start`
+#[cfg(feature = "tree-sitter")]
+fn point_after(start: Point, text: &str) -> Point {
+    match text.rfind('\n') {
+        Some(last_newline) => Point {
+            row: start.row + text.matches('\n').count(),
+            column: text.len() - last_newline - 1,
+        },
+        None => Point {
+            row: start.row,
+            column: start.column + text.len(),
+        },
+    }
+}
+
+#[cfg(feature = "tree-sitter")]
+fn get_attributes_from_tsnode(node: tree_sitter::Node, text: &[u8]) -> Result<Option<Vec<Attribute>>, ParseError> {
+    node.child_by_field_name("attributes")
+        .map(|attrs_node| {
+            let mut cursor = attrs_node.walk();
+            attrs_node
+                .named_children(&mut cursor)
+                .map(|attr_node| {
+                    let key = attr_node
+                        .child_by_field_name("key")
+                        .map(|node| node_text(node, text))
+                        .transpose()?;
+                    let val = attr_node
+                        .child_by_field_name("value")
+                        .map(|node| node_text(node, text))
+                        .transpose()?;
+                    match (key, val) {
+                        (None, None) => Ok(Attribute::Blank),
+                        (Some(key), None) => Ok(Attribute::Key(key.to_string())),
+                        (Some(key), Some(val)) => Ok(Attribute::KeyValue(key.to_string(), val.to_string())),
+                        (None, Some(_)) => Err(ParseError::unexpected(attr_node, "attribute key")),
+                    }
+                })
+                .collect()
         })
-        .flatten()
-        .collect()
+        .transpose()
 }
 
-fn get_attributes_from_tsnode(node: tree_sitter::Node, text: &[u8]) -> Option<Vec<Attribute>> {
-    node.child_by_field_name("attributes").map(|attrs_node| {
-        let mut cursor = attrs_node.walk();
-        attrs_node
-            .named_children(&mut cursor)
-            .map(|attr_node| {
-                let key = attr_node
-                    .child_by_field_name("key")
-                    .map(|node| node.utf8_text(text).unwrap());
-                let val = attr_node
-                    .child_by_field_name("value")
-                    .map(|node| node.utf8_text(text).unwrap());
-                match (key, val) {
-                    (None, None) => Attribute::Blank,
-                    (Some(key), None) => Attribute::Key(key.to_string()),
-                    (Some(key), Some(val)) => Attribute::KeyValue(key.to_string(), val.to_string()),
-                    _ => unreachable!(),
-                }
-            })
-            .collect()
-    })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::NorgLinkLocalTarget;
+
+    #[test]
+    fn test_split_table_row() {
+        let line = "| a | b \\| c |d";
+        let cells: Vec<_> = split_table_row(line)
+            .unwrap()
+            .into_iter()
+            .map(|(start, end)| line[start..end].trim())
+            .collect();
+        assert_eq!(cells, vec!["a", "b \\| c", "d"]);
+        assert_eq!(split_table_row("not a row | a |"), None);
+        assert!(is_delimiter_cell(" :---: "));
+        assert!(!is_delimiter_cell(" : "));
+    }
+
+    #[test]
+    fn test_parse_tag_params() {
+        assert_eq!(parse_tag_params(""), Vec::<String>::new());
+        assert_eq!(parse_tag_params("python"), vec!["python"]);
+        assert_eq!(parse_tag_params("  a\tb   c "), vec!["a", "b", "c"]);
+        assert_eq!(parse_tag_params("path/to\\ image.png"), vec!["path/to image.png"]);
+        assert_eq!(
+            parse_tag_params(r#"src.png "alt text \"quoted\"" """#),
+            vec!["src.png", "alt text \"quoted\"", ""]
+        );
+        assert_eq!(parse_tag_params("trailing\\"), vec!["trailing\\"]);
+    }
+
+    #[test]
+    fn test_serde_ast() {
+        let text = "* heading\n{* heading}[link] [anchor]{:file:}\n";
+        let ast = parse(text.as_bytes());
+        let json = serde_json::to_string(&ast).unwrap();
+        assert!(json.starts_with(r#"{"version":1,"blocks":"#));
+        assert!(json.contains(r#""anchors":{"anchor":{"#));
+        let back: NorgAST = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.blocks, ast.blocks);
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        let borrowed = try_parse_borrowed(text.as_bytes()).unwrap();
+        assert_eq!(serde_json::to_string(&borrowed).unwrap(), json);
+        let old = json.replacen(r#""version":1"#, r#""version":0"#, 1);
+        let err = serde_json::from_str::<NorgAST>(&old).unwrap_err();
+        assert!(err.to_string().contains("unsupported norg AST version 0"));
+    }
+
+    #[test]
+    fn test_parse_many() {
+        let texts = ["* a\n", "b\n", "- c\n"];
+        for mut parser in [NorgParser::new().unwrap(), NorgParser::pure_rust()] {
+            let results = parser.parse_many(&texts).unwrap();
+            for (text, result) in texts.iter().zip(results) {
+                assert_eq!(result.unwrap().blocks, parse(text.as_bytes()).blocks);
+            }
+            parser.set_cancellation_flag(Some(Arc::new(AtomicBool::new(true))));
+            let results = parser.parse_many(&texts).unwrap();
+            assert!(results
+                .iter()
+                .all(|result| matches!(result, Err(ParseError::Cancelled))));
+        }
+    }
+
+    #[test]
+    fn test_anchor_issues() {
+        let ast = parse(b"[a]{a.com} [b] [A]{b.com}\n");
+        assert_eq!(ast.anchor_issues.len(), 2);
+        let AnchorIssue::Dangling { id, .. } = &ast.anchor_issues[0] else {
+            panic!("expected dangling reference, got {:?}", ast.anchor_issues[0]);
+        };
+        assert_eq!(id, "b");
+        let AnchorIssue::Duplicate { id, first, second } = &ast.anchor_issues[1] else {
+            panic!("expected duplicate definition, got {:?}", ast.anchor_issues[1]);
+        };
+        assert_eq!(id, "a");
+        assert_eq!((first.start, second.start), (0, 15));
+        assert_eq!(
+            ast.anchors["a"].target,
+            NorgLinkTarget::Local(NorgLinkLocalTarget::Uri(String::from("a.com")))
+        );
+    }
 }
@@ -1,600 +1,1469 @@
-use std::{iter::Peekable, ops::Range};
+//! Pure-rust norg parser. Pick it with [`crate::parser::NorgParser::pure_rust`], it's also the
+//! default when built with `pure-rust-parser` feature or without `tree-sitter` feature.
+//!
+//! `Scanner` first splits the source into line based `FlatNode`s, which are then nested
+//! into sections, lists and ranged blocks. Produces the same AST as the tree-sitter parser;
+//! diagnostics use tree-sitter-norg node names as their kind.
+
+use std::{marker::PhantomData, rc::Rc};
 
-use crate::{block::{ListItem, NorgBlock}, inline::NorgInline};
 use unicode_categories::UnicodeCategories as _;
 
-fn flatatom_to_block(text: &str, block: Mark<AtomBlock>) -> NorgBlock {
-    match block.kind {
-        AtomBlock::BlankLine => {
-            unreachable!("indented line can't have blank line as content")
+use crate::{
+    block::{Heading, ListItem, NorgBlock, TableCell, TableRow, Tag, Todo},
+    borrowed,
+    diagnostic::{Diagnostic, Severity},
+    inline::{Attribute, NorgInline},
+    parser::{
+        is_delimiter_cell, parse_attribute_list, parse_tag_params, split_table_row,
+        CarryoverScanner, NorgAST, ParseError, ParseState, Point, Range, SourceText,
+    },
+    slug::{markup_text, slugify},
+    target::NorgLinkTarget,
+};
+
+/// byte range in the source
+type Span = std::ops::Range<usize>;
+
+/// Parse norg document.
+pub fn parse(text: &str) -> borrowed::NorgAST<'_> {
+    match parse_with_stop(text, &mut || None) {
+        Ok(ast) => ast,
+        Err(_) => unreachable!("parsing is only stopped by `stop`"),
+    }
+}
+
+/// Parse norg document, asking `stop` before each line whether to give up.
+pub(crate) fn parse_with_stop<'src, S: SourceText<'src>>(
+    text: &'src str,
+    stop: &mut dyn FnMut() -> Option<ParseError>,
+) -> Result<NorgAST<S>, ParseError> {
+    let lines = Rc::new(LineIndex::new(text));
+    let nodes = Scanner::new(text, lines.clone()).parse_flat(stop)?;
+    let mut builder = TreeBuilder {
+        source: text,
+        lines,
+        nodes,
+        next: 0,
+        state: ParseState::default(),
+        text: PhantomData,
+    };
+    let blocks = builder.blocks(0, None);
+    Ok(builder.state.finish(blocks))
+}
+
+/// Start byte of every line, to turn byte offsets into points
+struct LineIndex(Vec<usize>);
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        Self(
+            std::iter::once(0)
+                .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        )
+    }
+
+    fn point(&self, byte: usize) -> Point {
+        let row = self.0.partition_point(|&start| start <= byte) - 1;
+        Point {
+            row,
+            column: byte - self.0[row],
         }
-        AtomBlock::Heading { level, inline } => {
-            todo!("heading in list item is not implemented yet")
+    }
+
+    fn range(&self, span: Span) -> Range {
+        Range {
+            start: span.start,
+            end: span.end,
+            start_point: self.point(span.start),
+            end_point: self.point(span.end),
         }
-        AtomBlock::Paragraph => {
-            let mut inlines = vec![];
-            let mut scanner = Scanner::new(&text[block.span]);
-            while let Some(inline) = scanner.parse_inline() {
-                inlines.push(inline);
-            }
-            NorgBlock::Paragraph {
-                attrs: vec![],
-                inlines,
-            }
-        },
-        AtomBlock::InfirmTag { ident } => NorgBlock::InfirmTag {
-            name: text[ident.clone()].to_string(),
-            params: None,
-        },
-        AtomBlock::RangedTag { ident, content } => NorgBlock::RangedTag {
-            name: text[ident.clone()].to_string(),
-            params: None,
-            content: content
-                .iter()
-                .map(|line| text[line.clone()].to_string())
-                .collect(),
-        },
-        AtomBlock::CarryoverTag { ident } => NorgBlock::CarryoverTag {
-            name: text[ident.clone()].to_string(),
-            params: None,
-            target: todo!("should I parse carryover tag here?"),
-        },
     }
 }
 
-pub fn flatnodes_to_ao<I>(iter: &mut Peekable<I>, text: &str, lv: usize) -> Option<NorgBlock>
-where
-    I: Iterator<Item = FlatNode>,
-{
-    let flat = iter.peek()?;
-    match flat {
-        FlatNode::Block(block) => {
-            let block = block.clone();
-            match &block.kind {
-                AtomBlock::Heading { level, inline } => {
-                    let level = level.clone();
-                    if level <= lv {
-                        None
-                    } else {
-                        let mut contents = vec![];
-                        iter.next();
-                        while let Some(block) = flatnodes_to_ao(iter, text, level) {
-                            contents.push(block);
-                        }
-                        Some(NorgBlock::Section {
-                            attrs: vec![],
-                            level: level as u16,
-                            // TODO: convert inline to Option<Vec<NorgInline>>
-                            heading: None,
-                            contents,
-                        })
-                    }
+/// Nests flat nodes into blocks with text of type `S`
+struct TreeBuilder<'src, S> {
+    source: &'src str,
+    lines: Rc<LineIndex>,
+    nodes: Vec<FlatNode>,
+    /// index of the next node to consume
+    next: usize,
+    state: ParseState,
+    text: PhantomData<S>,
+}
+
+impl<'src, S: SourceText<'src>> TreeBuilder<'src, S> {
+    /// Blocks until a heading of `level` or above, closing modifier of `ranged` definition or
+    /// footnote, or the end of document.
+    fn blocks(&mut self, level: usize, ranged: Option<Detached>) -> Vec<NorgBlock<S>> {
+        let mut carryovers = CarryoverScanner::default();
+        let mut blocks = vec![];
+        while let Some(node) = self.nodes.get(self.next) {
+            match node {
+                FlatNode::Block(Mark {
+                    kind:
+                        AtomBlock::Heading {
+                            level: next_level, ..
+                        },
+                    ..
+                }) if *next_level <= level => break,
+                FlatNode::Block(Mark {
+                    kind: AtomBlock::RangedEnd { kind },
+                    ..
+                }) if Some(*kind) == ranged => break,
+                FlatNode::Indented { .. } => {
+                    let attrs = std::mem::take(&mut carryovers.attrs);
+                    let list = self.list(attrs);
+                    blocks.push(carryovers.apply_tags(list));
+                    continue;
                 }
+                FlatNode::Block(_) => {}
+            }
+            let FlatNode::Block(block) = self.nodes[self.next].clone() else {
+                unreachable!("indented nodes are handled above");
+            };
+            self.next += 1;
+            let attrs = std::mem::take(&mut carryovers.attrs);
+            let block = match block.kind {
                 AtomBlock::BlankLine => {
-                    iter.next();
-                    flatnodes_to_ao(iter, text, lv)
+                    carryovers.attrs = attrs;
+                    continue;
                 }
-                _ => {
-                    iter.next();
-                    Some(flatatom_to_block(text, block))
+                AtomBlock::CarryoverAttributes { .. } | AtomBlock::CarryoverTag { .. } => {
+                    carryovers.attrs = attrs;
+                    self.scan_carryover(&block, &mut carryovers);
+                    continue;
                 }
-            }
-        }
-        FlatNode::Indented(indent_mark, block) => {
-            let indent_kind = indent_mark.kind.clone();
-            let level = indent_mark.len();
-            let mut list_items = vec![ListItem {
-                attrs: vec![],
-                contents: block
-                    .clone()
-                    .map_or(vec![], |block| vec![flatatom_to_block(text, block)]),
-            }];
-            iter.next();
-            while let Some(next @ FlatNode::Indented(next_indent_mark, next_block)) = iter.peek() {
-                let next_indent_kind = next_indent_mark.kind.clone();
-                let next_level = next_indent_mark.len();
-                if next_level > level {
-                    // push sublist to last list item content
-                    let block = flatnodes_to_ao(iter, text, lv).unwrap();
-                    list_items.last_mut().unwrap().contents.push(block);
+                AtomBlock::RangedEnd { .. } => {
+                    carryovers.attrs = attrs;
+                    self.report_dropped(
+                        "closing modifier without opening one was dropped",
+                        block.span,
+                        "ranged_end",
+                    );
                     continue;
-                } else if next_level == level && next_indent_kind == Indent::Null {
-                    // push block to last list item's content
-                    if let Some(block) = next_block {
-                        let block = flatatom_to_block(text, block.clone());
-                        list_items.last_mut().unwrap().contents.push(block);
+                }
+                AtomBlock::Heading { level, .. } => {
+                    let heading = self.heading(&block);
+                    let contents = self.blocks(level, ranged);
+                    let end = contents
+                        .last()
+                        .map_or(heading.range.end, |block| block.range().end);
+                    NorgBlock::Section {
+                        attrs,
+                        level: level as u16,
+                        heading: Some(heading),
+                        contents,
+                        range: self.lines.range(block.span.start..end),
                     }
-                } else if next_level == level && indent_kind == next_indent_kind {
-                    // push sibling list item to current list
-                    list_items.push(ListItem {
-                        attrs: vec![],
-                        contents: next_block
-                            .clone()
-                            .map_or(vec![], |block| vec![flatatom_to_block(text, block)]),
-                    });
-                } else {
-                    // end current list
-                    break;
                 }
-                iter.next();
-            }
-            // rename for convenience
-            let attrs = vec![];
-            let items = list_items;
-            let level = level as u16;
-            Some(match indent_kind {
-                Indent::Unordered => NorgBlock::UnorderedList {
-                    attrs,
-                    level,
-                    items,
-                },
-                Indent::Ordered => NorgBlock::OrderedList {
-                    attrs,
-                    level,
-                    items,
-                },
-                Indent::Quote => NorgBlock::Quote {
+                AtomBlock::DetachedTitle {
+                    kind,
+                    ranged: true,
+                    title,
+                } => {
+                    let title = self.inlines(self.trim_newline(title));
+                    let contents = self.blocks(level, Some(kind));
+                    let end = match self.nodes.get(self.next) {
+                        Some(FlatNode::Block(
+                            end @ Mark {
+                                kind: AtomBlock::RangedEnd { .. },
+                                ..
+                            },
+                        )) => {
+                            let end = end.span.end;
+                            self.next += 1;
+                            end
+                        }
+                        _ => {
+                            let end = contents
+                                .last()
+                                .map_or(block.span.end, |block| block.range().end);
+                            self.report(
+                                Severity::Error,
+                                "ranged block is not closed",
+                                block.span.start..end,
+                                kind.ranged_node(),
+                            );
+                            end
+                        }
+                    };
+                    self.detached(kind, attrs, title, contents, block.span.start..end)
+                }
+                AtomBlock::DetachedTitle {
+                    kind,
+                    ranged: false,
+                    title,
+                } => {
+                    let title = self.inlines(self.trim_newline(title));
+                    // content of single definition or footnote is the paragraph that follows
+                    let mut contents = vec![];
+                    if let Some(FlatNode::Block(
+                        paragraph @ Mark {
+                            kind: AtomBlock::Paragraph,
+                            ..
+                        },
+                    )) = self.nodes.get(self.next)
+                    {
+                        let paragraph = paragraph.clone();
+                        self.next += 1;
+                        contents.push(self.atom(paragraph, vec![]));
+                    }
+                    let end = contents
+                        .last()
+                        .map_or(block.span.end, |block| block.range().end);
+                    self.detached(kind, attrs, title, contents, block.span.start..end)
+                }
+                _ => self.atom(block, attrs),
+            };
+            blocks.push(carryovers.apply_tags(block));
+        }
+        carryovers.finish(&mut self.state);
+        blocks
+    }
+
+    /// Single line block, or block that doesn't take following nodes as its content.
+    fn atom(&mut self, block: Mark<AtomBlock>, attrs: Vec<Attribute>) -> NorgBlock<S> {
+        let span = self.trim_newline(block.span.clone());
+        match block.kind {
+            AtomBlock::InfirmTag { ident, params } => NorgBlock::InfirmTag {
+                attrs,
+                params: self.params(params),
+                name: self.source[ident].into(),
+                range: self.lines.range(span),
+            },
+            AtomBlock::RangedTag {
+                ident,
+                params,
+                content,
+                closed,
+            } => {
+                let name = &self.source[ident];
+                if !closed {
+                    self.report(
+                        Severity::Error,
+                        format!("ranged tag `@{name}` is not closed with `@end`"),
+                        span.clone(),
+                        "ranged_tag",
+                    );
+                }
+                if name == "table" {
+                    match self.table_rows(&content) {
+                        Some(rows) => {
+                            return NorgBlock::Table {
+                                attrs,
+                                rows,
+                                range: self.lines.range(span),
+                            }
+                        }
+                        None => self.report(
+                            Severity::Warning,
+                            "`@table` content is not a pipe-delimited table",
+                            span.clone(),
+                            "ranged_tag",
+                        ),
+                    }
+                }
+                NorgBlock::RangedTag {
                     attrs,
-                    level,
-                    items,
-                },
-                Indent::Null => todo!(),
-            })
+                    params: self.params(params),
+                    name: name.into(),
+                    content: content
+                        .into_iter()
+                        .map(|line| self.source[line].into())
+                        .collect(),
+                    range: self.lines.range(span),
+                }
+            }
+            AtomBlock::HorizontalLine => NorgBlock::HorizontalLine {
+                attrs,
+                range: self.lines.range(span),
+            },
+            AtomBlock::Heading { level, .. } => NorgBlock::Section {
+                attrs,
+                level: level as u16,
+                heading: Some(self.heading(&block)),
+                contents: vec![],
+                range: self.lines.range(span),
+            },
+            AtomBlock::DetachedTitle { kind, title, .. } => {
+                let title = self.inlines(self.trim_newline(title));
+                self.detached(kind, attrs, title, vec![], span)
+            }
+            // carryovers and closing modifiers only mean something on their own line
+            AtomBlock::Paragraph
+            | AtomBlock::CarryoverAttributes { .. }
+            | AtomBlock::CarryoverTag { .. }
+            | AtomBlock::RangedEnd { .. }
+            | AtomBlock::BlankLine => NorgBlock::Paragraph {
+                attrs,
+                inlines: self.inlines(span.clone()),
+                range: self.lines.range(span),
+            },
+        }
+    }
+
+    fn heading(&mut self, block: &Mark<AtomBlock>) -> Heading<S> {
+        let AtomBlock::Heading { attrs, title, .. } = &block.kind else {
+            unreachable!("only called with headings");
+        };
+        let attrs = self.attrs(attrs.clone());
+        let inlines = match title {
+            Some(title) => self.inlines(self.trim_newline(title.clone())),
+            None => vec![],
+        };
+        Heading {
+            todo: Todo::from_attrs(&attrs),
+            id: self.state.slugger.unique(&markup_text(&inlines)),
+            attrs,
+            inlines,
+            range: self.lines.range(self.trim_newline(block.span.clone())),
+        }
+    }
+
+    fn detached(
+        &mut self,
+        kind: Detached,
+        attrs: Vec<Attribute>,
+        title: Vec<NorgInline<S>>,
+        contents: Vec<NorgBlock<S>>,
+        span: Span,
+    ) -> NorgBlock<S> {
+        let range = self.lines.range(self.trim_newline(span));
+        match kind {
+            Detached::Definition => NorgBlock::Definition {
+                attrs,
+                title,
+                contents,
+                range,
+            },
+            Detached::Footnote => NorgBlock::Footnote {
+                attrs,
+                id: self.state.footnote_slugger.unique(&markup_text(&title)),
+                title,
+                contents,
+                range,
+            },
+        }
+    }
+
+    /// List starting at the next node, with its nested lists. Carryovers right before an item
+    /// apply to that item.
+    fn list(&mut self, attrs: Vec<Attribute>) -> NorgBlock<S> {
+        let FlatNode::Indented { indent, .. } = &self.nodes[self.next] else {
+            unreachable!("list starts at indented node");
+        };
+        let (kind, level, start) = (indent.kind, indent.len(), indent.span.start);
+        let mut carryovers = CarryoverScanner::default();
+        let mut items = vec![];
+        loop {
+            let mut item_start = self.next;
+            while let Some(FlatNode::Block(Mark {
+                kind: AtomBlock::CarryoverAttributes { .. } | AtomBlock::CarryoverTag { .. },
+                ..
+            })) = self.nodes.get(item_start)
+            {
+                item_start += 1;
+            }
+            match self.nodes.get(item_start) {
+                Some(FlatNode::Indented { indent, .. })
+                    if indent.kind == kind && indent.len() == level => {}
+                _ => break,
+            }
+            while self.next < item_start {
+                let FlatNode::Block(block) = self.nodes[self.next].clone() else {
+                    unreachable!("only carryovers are skipped");
+                };
+                self.scan_carryover(&block, &mut carryovers);
+                self.next += 1;
+            }
+            items.push(self.list_item(level, &mut carryovers));
+        }
+        let end = items
+            .last()
+            .map_or(start, |item: &ListItem<S>| item.range.end);
+        let range = self.lines.range(start..end);
+        let level = level as u16;
+        match kind {
+            Indent::Unordered => NorgBlock::UnorderedList {
+                attrs,
+                level,
+                items,
+                range,
+            },
+            Indent::Ordered => NorgBlock::OrderedList {
+                attrs,
+                level,
+                items,
+                range,
+            },
+            Indent::Quote => NorgBlock::Quote {
+                attrs,
+                level,
+                items,
+                range,
+            },
+        }
+    }
+
+    fn list_item(&mut self, level: usize, carryovers: &mut CarryoverScanner<S>) -> ListItem<S> {
+        let FlatNode::Indented {
+            indent,
+            attrs: item_attrs,
+            block,
+        } = self.nodes[self.next].clone()
+        else {
+            unreachable!("list item starts at indented node");
+        };
+        self.next += 1;
+        let item_attrs = self.attrs(item_attrs);
+        let todo = Todo::from_attrs(&item_attrs);
+        let mut attrs = std::mem::take(&mut carryovers.attrs);
+        attrs.extend(item_attrs);
+        let mut contents = vec![];
+        if let Some(block) = block {
+            contents.push(self.atom(block, vec![]));
+        }
+        // deeper items make a list nested in this item
+        while let Some(FlatNode::Indented { indent, .. }) = self.nodes.get(self.next) {
+            if indent.len() <= level {
+                break;
+            }
+            contents.push(self.list(vec![]));
+        }
+        let end = contents
+            .last()
+            .map_or(indent.span.end, |block| block.range().end);
+        ListItem {
+            attrs,
+            todo,
+            tags: std::mem::take(&mut carryovers.tags),
+            contents,
+            range: self.lines.range(indent.span.start..end),
+        }
+    }
+
+    fn scan_carryover(&mut self, block: &Mark<AtomBlock>, carryovers: &mut CarryoverScanner<S>) {
+        match &block.kind {
+            AtomBlock::CarryoverAttributes { attrs } => {
+                carryovers
+                    .attrs
+                    .extend(parse_attribute_list(&self.source[attrs.clone()]));
+            }
+            AtomBlock::CarryoverTag { ident, params } => carryovers.tags.push(Tag {
+                name: self.source[ident.clone()].into(),
+                params: self.params(params.clone()),
+                range: self.lines.range(self.trim_newline(block.span.clone())),
+            }),
+            _ => {}
+        }
+    }
+
+    /// Rows of `@table` content, see `tsnode_to_table_rows` in [`crate::parser`].
+    fn table_rows(&mut self, lines: &[Span]) -> Option<Vec<TableRow<S>>> {
+        let mut rows = vec![];
+        let mut header_end = None;
+        for line in lines {
+            let line = self.trim_newline(line.clone());
+            let line_text = &self.source[line.clone()];
+            if line_text.trim().is_empty() {
+                continue;
+            }
+            let cells = split_table_row(line_text)?;
+            if cells
+                .iter()
+                .all(|&(start, end)| is_delimiter_cell(&line_text[start..end]))
+            {
+                header_end.get_or_insert(rows.len());
+                continue;
+            }
+            let cells = cells
+                .into_iter()
+                .map(|(start, end)| self.table_cell(line.start + start..line.start + end))
+                .collect();
+            rows.push(TableRow {
+                header: false,
+                cells,
+                range: self.lines.range(line),
+            });
+        }
+        for row in rows.iter_mut().take(header_end.unwrap_or(0)) {
+            row.header = true;
+        }
+        Some(rows)
+    }
+
+    fn table_cell(&mut self, span: Span) -> TableCell<S> {
+        let cell = &self.source[span.clone()];
+        let mut start = span.start + (cell.len() - cell.trim_start().len());
+        let end = span.start + cell.trim_end().len();
+        let mut attrs = vec![];
+        if let Some((attrs_text, rest)) = self.source[start..end]
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+        {
+            attrs = parse_attribute_list(attrs_text);
+            start = end - rest.trim_start().len();
+        }
+        TableCell {
+            attrs,
+            inlines: self.inlines(start..end),
+            range: self.lines.range(span),
+        }
+    }
+
+    fn inlines(&mut self, span: Span) -> Vec<NorgInline<S>> {
+        let inlines = Scanner::inline(self.source, self.lines.clone(), span).parse_inlines();
+        register_anchors(&mut self.state, &inlines);
+        inlines
+    }
+
+    /// attributes in `(...)` span
+    fn attrs(&self, span: Option<Span>) -> Vec<Attribute> {
+        span.map_or(vec![], |span| {
+            parse_attribute_list(&self.source[span.start + 1..span.end - 1])
+        })
+    }
+
+    fn params(&self, span: Option<Span>) -> Vec<String> {
+        span.map_or(vec![], |span| parse_tag_params(&self.source[span]))
+    }
+
+    fn trim_newline(&self, span: Span) -> Span {
+        let text = self.source[span.clone()].trim_end_matches(['\r', '\n']);
+        span.start..span.start + text.len()
+    }
+
+    fn report(&mut self, severity: Severity, message: impl Into<String>, span: Span, kind: &str) {
+        self.state.diagnostics.push(Diagnostic {
+            severity,
+            message: message.into(),
+            range: self.lines.range(span),
+            kind: kind.to_string(),
+            dropped: false,
+        });
+    }
+
+    /// warn about source that didn't make it to the AST
+    fn report_dropped(&mut self, message: impl Into<String>, span: Span, kind: &str) {
+        self.state.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            range: self.lines.range(span),
+            kind: kind.to_string(),
+            dropped: true,
+        });
+    }
+}
+
+/// Add anchors in `inlines` to `state`, in document order.
+fn register_anchors<S>(state: &mut ParseState, inlines: &[NorgInline<S>]) {
+    use NorgInline::*;
+    for inline in inlines {
+        match inline {
+            Anchor {
+                target,
+                markup,
+                id,
+                range,
+                ..
+            } => {
+                register_anchors(state, markup);
+                state.add_anchor(id, target.as_ref(), *range);
+            }
+            Bold { markup, .. }
+            | Italic { markup, .. }
+            | Underline { markup, .. }
+            | Strikethrough { markup, .. }
+            | Verbatim { markup, .. }
+            | Spoiler { markup, .. }
+            | Superscript { markup, .. }
+            | Subscript { markup, .. }
+            | InlineMath { markup, .. }
+            | Variable { markup, .. }
+            | NullModifier { markup, .. } => register_anchors(state, markup),
+            Macro { markup, .. } | Link { markup, .. } => {
+                register_anchors(state, markup.as_deref().unwrap_or_default())
+            }
+            Text { .. }
+            | Special { .. }
+            | Escape { .. }
+            | Whitespace { .. }
+            | SoftBreak { .. }
+            | HardBreak { .. } => {}
         }
     }
 }
 
-pub struct Scanner<'src> {
+pub(crate) struct Scanner<'src> {
     source: &'src str,
     pos: usize,
+    /// scanning stops here, so inline scanner only sees its own paragraph or title
+    end: usize,
+    lines: Rc<LineIndex>,
     inline_stack: Vec<InlineMarkupKind>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub enum InlineMarkupKind {
+pub(crate) enum InlineMarkupKind {
     Bold,
     Italic,
     Underline,
     Strikethrough,
-    Markup,
-    Target,
+    Spoiler,
+    Superscript,
+    Subscript,
+    Verbatim,
+    InlineMath,
+    Variable,
+    NullModifier,
+    /// `[markup]` of anchors, links and macros
+    Bracket,
+}
+
+impl InlineMarkupKind {
+    fn from_modifier(ch: char) -> Option<Self> {
+        Some(match ch {
+            '*' => Self::Bold,
+            '/' => Self::Italic,
+            '_' => Self::Underline,
+            '~' => Self::Strikethrough,
+            '!' => Self::Spoiler,
+            '^' => Self::Superscript,
+            ',' => Self::Subscript,
+            '`' => Self::Verbatim,
+            '$' => Self::InlineMath,
+            '&' => Self::Variable,
+            '%' => Self::NullModifier,
+            _ => return None,
+        })
+    }
+
+    fn closing_char(self) -> char {
+        match self {
+            Self::Bold => '*',
+            Self::Italic => '/',
+            Self::Underline => '_',
+            Self::Strikethrough => '~',
+            Self::Spoiler => '!',
+            Self::Superscript => '^',
+            Self::Subscript => ',',
+            Self::Verbatim => '`',
+            Self::InlineMath => '$',
+            Self::Variable => '&',
+            Self::NullModifier => '%',
+            Self::Bracket => ']',
+        }
+    }
+
+    /// content can't have nested markup
+    fn is_verbatim(self) -> bool {
+        matches!(self, Self::Verbatim | Self::InlineMath | Self::Variable)
+    }
+}
+
+/// Closing modifier of the markup being scanned
+#[derive(Copy, Clone, Debug)]
+struct Closing {
+    kind: InlineMarkupKind,
+    /// closed with `|X` instead of `X`
+    free_form: bool,
+}
+
+/// Result of scanning one inline token
+enum InlineStep<S> {
+    Node(NorgInline<S>),
+    /// closing modifier of the innermost markup
+    Close,
+    End,
 }
 
 #[derive(Clone, Debug)]
-pub enum FlatNode {
-    Indented(Mark<Indent>, Option<Mark<AtomBlock>>),
+pub(crate) enum FlatNode {
+    /// line starting with list or quote prefix, with `(attrs)` and the block following it
+    Indented {
+        indent: Mark<Indent>,
+        attrs: Option<Span>,
+        block: Option<Mark<AtomBlock>>,
+    },
     Block(Mark<AtomBlock>),
 }
 
+impl FlatNode {
+    fn paragraph_mut(&mut self) -> Option<&mut Mark<AtomBlock>> {
+        match self {
+            Self::Block(block)
+            | Self::Indented {
+                block: Some(block), ..
+            } if block.kind == AtomBlock::Paragraph => Some(block),
+            _ => None,
+        }
+    }
+}
+
 impl<'src> Scanner<'src> {
-    pub fn new(source: &'src str) -> Self {
-        Self { source, pos: 0, inline_stack: vec![] }
+    fn new(source: &'src str, lines: Rc<LineIndex>) -> Self {
+        Self {
+            source,
+            pos: 0,
+            end: source.len(),
+            lines,
+            inline_stack: vec![],
+        }
     }
 
-    pub fn parse_flat(&mut self) -> Vec<FlatNode> {
-        let mut blocks: Vec<FlatNode> = vec![];
-        loop {
-            let flat_node = self.parse_node();
-            if let FlatNode::Block(block) = &flat_node {
-                if block.span.is_empty() {
-                    break;
-                }
+    /// scanner for inline markup in `span`
+    fn inline(source: &'src str, lines: Rc<LineIndex>, span: Span) -> Self {
+        Self {
+            source,
+            pos: span.start,
+            end: span.end,
+            lines,
+            inline_stack: vec![],
+        }
+    }
+
+    fn parse_flat(
+        &mut self,
+        stop: &mut dyn FnMut() -> Option<ParseError>,
+    ) -> Result<Vec<FlatNode>, ParseError> {
+        let mut nodes: Vec<FlatNode> = vec![];
+        while self.pos < self.end {
+            if let Some(err) = stop() {
+                return Err(err);
             }
-            if let (
-                Some(FlatNode::Indented(_, Some(prev_block)) | FlatNode::Block(prev_block)),
-                FlatNode::Block(block),
-            ) = (blocks.last_mut(), &flat_node)
-            {
-                if prev_block.kind == AtomBlock::Paragraph
-                    && block.kind == AtomBlock::Paragraph
-                {
-                    // extend paragraph
-                    println!("append {:?}", block.span);
-                    prev_block.span = prev_block.span.start..block.span.end;
-                    self.pos = block.span.end;
+            let start = self.pos;
+            let node = self.parse_node();
+            if self.pos == start {
+                // nothing was scanned, skip a character rather than loop on it
+                self.pos += self.current_char().map_or(1, char::len_utf8);
+                continue;
+            }
+            if let FlatNode::Block(block) = &node {
+                if block.span.is_empty() {
+                    // trailing whitespace at the end of document
                     continue;
                 }
+                if block.kind == AtomBlock::Paragraph {
+                    if let Some(paragraph) = nodes.last_mut().and_then(FlatNode::paragraph_mut) {
+                        // paragraph continues on the next line
+                        paragraph.span.end = block.span.end;
+                        continue;
+                    }
+                }
             }
-            self.pos = match &flat_node {
-                FlatNode::Indented(_, Some(block)) => block.span.end,
-                FlatNode::Indented(indent, None) => self.pos, // don't update
-                FlatNode::Block(block) => block.span.end,
-            };
-            blocks.push(flat_node);
+            nodes.push(node);
         }
-        blocks
+        Ok(nodes)
     }
 
+    /// Scan a line (or lines, for ranged tags) and move past it.
     fn parse_node(&mut self) -> FlatNode {
         self.skip_whitespace();
-        let indent = self.parse_indent();
-        if let Some(indent) = indent {
-            let whitespace = self.lex_common_at(indent.span.end);
-            self.pos = whitespace.span.end;
-            match whitespace.kind {
-                CommonToken::Space => {
-                    // NOTE: parse potential attributes (`(...`)
-                    // TODO: update the `indent` to `(indent, attrs)`
-                    let block = self.parse_pb_block().unwrap_or(Mark::new(AtomBlock::Paragraph, self.lex_line(self.pos)));
-                    return FlatNode::Indented(indent, Some(block));
+        if let Some(indent) = self.parse_indent() {
+            self.pos = indent.span.end;
+            self.skip_whitespace();
+            let attrs = self.parse_attrs_at(self.pos);
+            if let Some(attrs) = &attrs {
+                self.pos = attrs.end;
+                self.skip_whitespace();
+            }
+            let block = match self.lex_common_at(self.pos) {
+                Mark {
+                    kind: CommonToken::Newline | CommonToken::Eof,
+                    span,
+                } => {
+                    self.pos = span.end;
+                    None
                 }
-                CommonToken::Newline | CommonToken::Eof => {
-                    // TODO: I'm not sure if I should allow newline between indent prefix and the
-                    // content or not. It makes sence for null indents, but doesn't make sense for
-                    // normal list items:
-                    // ```
-                    // - <|cursor|>
-                    // paragraph. oops I'm belong to the list now
-                    // ```
-                    // actually wait, isn't it just what multi-line paragraphs do?
-                    // like... if I start typing something there, the paragraph below will be part
-                    // of list item anyways.
-                    return FlatNode::Indented(indent, None);
+                _ => {
+                    let block = self.parse_block();
+                    self.pos = block.span.end;
+                    Some(block)
                 }
-                _ => unreachable!("parse_indent success only when next token is whitespace"),
-            }
-        } else {
-            let block = self.parse_pb_block().unwrap_or(Mark::new(AtomBlock::Paragraph, self.lex_line(self.pos)));
-            FlatNode::Block(block)
+            };
+            return FlatNode::Indented {
+                indent,
+                attrs,
+                block,
+            };
         }
+        let block = self.parse_block();
+        self.pos = block.span.end;
+        FlatNode::Block(block)
+    }
+
+    fn parse_block(&self) -> Mark<AtomBlock> {
+        self.parse_pb_block()
+            .unwrap_or_else(|| Mark::new(AtomBlock::Paragraph, self.lex_line(self.pos)))
     }
 
     fn skip_whitespace(&mut self) {
-        loop {
-            let next = self.char_at(self.pos);
-            if next != ' ' {
-                break;
-            }
-            self.pos += next.len_utf8();
+        while matches!(self.current_char(), Some(' ' | '\t')) {
+            self.pos += 1;
         }
     }
 
     fn parse_indent(&self) -> Option<Mark<Indent>> {
-        let first = self.current_char();
+        let first = self.current_char()?;
         let Ok(indent_kind) = Indent::try_from(first) else {
             return None;
         };
         let mut pos = self.pos;
-        while self.char_at(pos) == first {
+        while self.char_at(pos) == Some(first) {
             pos += 1;
         }
-        let _count = pos - self.pos;
-        if !self.char_at(pos).is_whitespace() {
+        if !matches!(self.char_at(pos), Some(' ' | '\t')) {
             return None;
         }
-        Some(Mark::new(
-            indent_kind,
-            self.pos..pos,
-        ))
+        Some(Mark::new(indent_kind, self.pos..pos))
+    }
+
+    /// `(...)` at `pos`, followed by whitespace or the end of input
+    fn parse_attrs_at(&self, pos: usize) -> Option<Span> {
+        if self.char_at(pos) != Some('(') {
+            return None;
+        }
+        let line = self.lex_line(pos);
+        let close = self.source[line].find(')')? + pos;
+        let end = close + 1;
+        self.char_at(end)
+            .is_none_or(char::is_whitespace)
+            .then_some(pos..end)
     }
 
     fn parse_pb_block(&self) -> Option<Mark<AtomBlock>> {
-        let first = self.current_char();
+        let Some(first) = self.current_char() else {
+            return Some(Mark::new(AtomBlock::BlankLine, self.pos..self.pos));
+        };
+        let count = self.count_repeated(self.pos, first);
+        let after = self.pos + count;
         match first {
             '*' => {
-                let mut pos = self.pos;
-                while self.char_at(pos) == first {
-                    pos += 1;
-                }
-                let count = pos - self.pos;
-                let next = self.lex_common_at(pos);
-                pos += next.len();
+                let next = self.lex_common_at(after);
                 match next.kind {
                     CommonToken::Space => {
-                        if self.char_at(pos) == '(' {
-                            todo!("parse attributes");
+                        let mut pos = next.span.end;
+                        let attrs = self.parse_attrs_at(pos);
+                        if let Some(attrs_span) = &attrs {
+                            let next = self.lex_common_at(attrs_span.end);
+                            if next.kind != CommonToken::Space {
+                                // `* (x)` without title
+                                return Some(Mark::new(
+                                    AtomBlock::Heading {
+                                        level: count,
+                                        attrs,
+                                        title: None,
+                                    },
+                                    self.pos..next.span.end,
+                                ));
+                            }
+                            pos = next.span.end;
                         }
-                        let title_span = self.lex_line(pos);
-                        let span = self.pos..title_span.end;
+                        let line = self.lex_line(pos);
                         Some(Mark::new(
                             AtomBlock::Heading {
                                 level: count,
-                                inline: Some(title_span),
+                                attrs,
+                                title: Some(line.clone()),
                             },
-                            span,
+                            self.pos..line.end,
                         ))
                     }
-                    CommonToken::Newline | CommonToken::Eof => {
-                        let span = self.pos..next.span.end;
-                        Some(Mark::new(
-                            AtomBlock::Heading {
-                                level: count,
-                                inline: None,
-                            },
-                            span,
-                        ))
-                    }
-                    _ => None
+                    CommonToken::Newline | CommonToken::Eof => Some(Mark::new(
+                        AtomBlock::Heading {
+                            level: count,
+                            attrs: None,
+                            title: None,
+                        },
+                        self.pos..next.span.end,
+                    )),
+                    _ => None,
                 }
             }
             '.' | '#' => {
-                let pos = self.pos + 1;
-                let next = self.char_at(pos);
-                if next.is_whitespace() {
-                    return None;
-                }
-                if (first, next) == ('#', '(') {
-                    // block attribute
-                    todo!("parse attributes")
-                } else {
-                    // infirm/carryover tags
-                    let line_span = self.lex_line(pos);
-                    let span = self.pos..line_span.end;
-                    let (ident, param) = {
-                        let mut res = self.parse_tag_line(line_span).into_iter();
-                        (res.next().unwrap(), res.next())
-                    };
-                    Some(Mark::new(
-                        match first {
-                            '#' => AtomBlock::CarryoverTag { ident },
-                            '.' => AtomBlock::InfirmTag { ident },
-                            _ => unreachable!(),
+                let next = self.char_at(self.pos + 1);
+                if (first, next) == ('#', Some('(')) {
+                    let attrs = self.parse_attrs_at(self.pos + 1)?;
+                    let line = self.lex_line(self.pos);
+                    if !self.source[attrs.end..line.end].trim().is_empty() {
+                        return None;
+                    }
+                    return Some(Mark::new(
+                        AtomBlock::CarryoverAttributes {
+                            attrs: attrs.start + 1..attrs.end - 1,
                         },
-                        span,
-                    ))
+                        line,
+                    ));
                 }
+                if !next.is_some_and(char::is_alphanumeric) {
+                    return None;
+                }
+                let line = self.lex_line(self.pos);
+                let (ident, params) = self.parse_tag_line(self.pos + 1..line.end);
+                Some(Mark::new(
+                    match first {
+                        '#' => AtomBlock::CarryoverTag { ident, params },
+                        '.' => AtomBlock::InfirmTag { ident, params },
+                        _ => unreachable!(),
+                    },
+                    line,
+                ))
             }
             '@' => {
-                let mut pos = self.pos + 1;
-                let next = self.char_at(pos);
-                if next.is_whitespace() {
+                if !self
+                    .char_at(self.pos + 1)
+                    .is_some_and(char::is_alphanumeric)
+                {
                     return None;
                 }
-                let line_span = self.lex_line(pos);
-                pos = line_span.end;
-                let (ident, param) = {
-                    let mut res = self.parse_tag_line(line_span).into_iter();
-                    (res.next().unwrap(), res.next())
-                };
+                let line = self.lex_line(self.pos);
+                let (ident, params) = self.parse_tag_line(self.pos + 1..line.end);
+                let mut pos = line.end;
                 let mut content = vec![];
-                loop {
+                let closed = loop {
                     let line = self.lex_line(pos);
                     if line.is_empty() {
-                        todo!("handle ranged tag with missing end modifier");
+                        break false;
                     }
                     pos = line.end;
-                    let line_str = &self.source[line.clone()];
-                    if line_str.trim() == "@end" {
-                        break;
+                    if self.source[line.clone()].trim() == "@end" {
+                        break true;
                     }
                     content.push(line);
-                }
+                };
                 Some(Mark::new(
                     AtomBlock::RangedTag {
                         ident,
-                        content
+                        params,
+                        content,
+                        closed,
                     },
-                    (self.pos)..pos,
+                    self.pos..pos,
                 ))
             }
+            '_' if count >= 3 => {
+                let line = self.lex_line(self.pos);
+                self.source[after..line.end]
+                    .trim()
+                    .is_empty()
+                    .then_some(Mark::new(AtomBlock::HorizontalLine, line))
+            }
+            '$' | '^' if count <= 2 => {
+                let kind = match first {
+                    '$' => Detached::Definition,
+                    _ => Detached::Footnote,
+                };
+                let next = self.lex_common_at(after);
+                match next.kind {
+                    CommonToken::Space => {
+                        let line = self.lex_line(next.span.end);
+                        Some(Mark::new(
+                            AtomBlock::DetachedTitle {
+                                kind,
+                                ranged: count == 2,
+                                title: line.clone(),
+                            },
+                            self.pos..line.end,
+                        ))
+                    }
+                    CommonToken::Newline | CommonToken::Eof if count == 2 => Some(Mark::new(
+                        AtomBlock::RangedEnd { kind },
+                        self.pos..next.span.end,
+                    )),
+                    _ => None,
+                }
+            }
             '\r' | '\n' => {
                 let mark = self.lex_common_at(self.pos);
-                debug_assert_eq!(mark.kind, CommonToken::Newline);
-                Some(Mark::new(
-                    AtomBlock::BlankLine,
-                    mark.span,
-                ))
+                Some(Mark::new(AtomBlock::BlankLine, mark.span))
             }
             _ => None,
         }
     }
 
-    fn parse_tag_line(&self, line_span: Range<usize>) -> Vec<Range<usize>> {
-        let mut pos = line_span.start;
+    /// Tag name and raw parameters in `span` of a tag line, without the tag prefix.
+    fn parse_tag_line(&self, span: Span) -> (Span, Option<Span>) {
+        let text = self.source[span.clone()].trim_end();
+        let ident_len = text.find(char::is_whitespace).unwrap_or(text.len());
+        let ident = span.start..span.start + ident_len;
+        let params = text[ident_len..].trim_start();
+        let params = (!params.is_empty())
+            .then(|| span.start + text.len() - params.len()..span.start + text.len());
+        (ident, params)
+    }
+
+    fn count_repeated(&self, pos: usize, ch: char) -> usize {
+        let mut end = pos;
+        while self.char_at(end) == Some(ch) {
+            end += ch.len_utf8();
+        }
+        (end - pos) / ch.len_utf8()
+    }
+
+    fn parse_inlines<S: SourceText<'src>>(&mut self) -> Vec<NorgInline<S>> {
+        self.parse_markup(None).unwrap_or_default()
+    }
+
+    /// Parse inlines until the `closing` modifier, or to the end when there is none. Returns
+    /// `None` when `closing` never shows up.
+    fn parse_markup<S: SourceText<'src>>(
+        &mut self,
+        closing: Option<Closing>,
+    ) -> Option<Vec<NorgInline<S>>> {
+        let mut markup = vec![];
         loop {
-            let ch = self.char_at(pos);
-            if ch.is_whitespace() {
-                break;
-            }
-            pos += ch.len_utf8();
-        }
-        let mut res = vec![];
-        let ident = line_span.start..pos;
-        res.push(ident);
-        if !matches!(self.char_at(pos), '\n' | '\0') {
-            loop {
-                let ch = self.char_at(pos);
-                if ch != ' ' {
-                    break;
-                }
-                pos += ch.len_utf8();
+            match self.parse_inline(closing) {
+                InlineStep::Node(inline) => markup.push(inline),
+                InlineStep::Close => return Some(markup),
+                InlineStep::End => return closing.is_none().then_some(markup),
             }
-            let param = self.lex_line(pos);
-            res.push(param);
         }
-        res
     }
 
-    /// parse inline nodes
-    /// NOTE: this will continue until end of source
-    fn parse_inline(&mut self) -> Option<NorgInline> {
-        let tk = self.lex_common_at(self.pos);
-        use CommonToken::*;
+    fn parse_inline<S: SourceText<'src>>(&mut self, closing: Option<Closing>) -> InlineStep<S> {
+        let start = self.pos;
+        let tk = self.lex_common_at(start);
         self.pos = tk.span.end;
-        match tk.kind {
-            Space => Some(NorgInline::Whitespace),
-            Newline => Some(NorgInline::SoftBreak),
-            Eof => None,
-            Text => Some(NorgInline::Text(self.source[tk.span].to_string())),
-            Special(']') => {
-                if self.inline_stack.last() == Some(&InlineMarkupKind::Markup) {
-                    self.inline_stack.pop();
-                    None
-                } else {
-                    Some(NorgInline::Special(']'.to_string()))
-                }
+        let range = self.lines.range(tk.span.clone());
+        let ch = match tk.kind {
+            CommonToken::Eof => return InlineStep::End,
+            CommonToken::Space => return InlineStep::Node(NorgInline::Whitespace { range }),
+            CommonToken::Newline => return InlineStep::Node(NorgInline::SoftBreak { range }),
+            CommonToken::Text => {
+                return InlineStep::Node(NorgInline::Text {
+                    text: tk.text(self.source).into(),
+                    range,
+                })
             }
-            Special('[') => {
-                self.inline_stack.push(InlineMarkupKind::Markup);
-                let mut markup = vec![];
-                while let Some(inline) = self.parse_inline() {
-                    markup.push(inline);
-                }
-                // TODO: uhh how to parse attributes with this pattern?
-                Some(NorgInline::Anchor { target: None, markup, attrs: vec![] })
+            CommonToken::Special(ch) => ch,
+        };
+        let text = tk.text(self.source);
+        let special = || NorgInline::Special {
+            text: text.into(),
+            range,
+        };
+        if let Some(closing) = closing {
+            if self.is_closing(start, closing) {
+                self.pos = start + if closing.free_form { 2 } else { 1 };
+                return InlineStep::Close;
             }
-            Special(ch @ ('*' | '/' | '_' | '~')) => {
-                // 1. don't care about previous token. repeated token should have been already
-                //    consumed from previous iteration
-                // 2. lookahead and check if next character mets the requirements
-                let kind = match ch {
-                    '*' => InlineMarkupKind::Bold,
-                    '/' => InlineMarkupKind::Italic,
-                    '_' => InlineMarkupKind::Underline,
-                    '~' => InlineMarkupKind::Strikethrough,
-                    _ => unreachable!(),
-                };
-                let next_ch = self.char_at(self.pos);
-                if next_ch == ch {
-                    // repeated punctuations
-                    let mut count = 1;
-                    while self.char_at(self.pos) == ch {
-                        self.pos += ch.len_utf8();
-                        count += 1;
-                    }
-                    Some(NorgInline::Special(ch.to_string().repeat(count)))
-                } else if self.inline_stack.last() == Some(&kind) && next_ch.is_whitespace() {
-                    // closing modifier
-                    self.inline_stack.pop();
-                    None
-                } else if !self.inline_stack.contains(&kind) && !next_ch.is_whitespace() {
-                    // opening modifier
-                    self.inline_stack.push(kind);
-                    let mut markup = vec![];
-                    while let Some(inline) = self.parse_inline() {
-                        markup.push(inline);
-                    }
-                    let attrs = vec![];
-                    Some(match kind {
-                        InlineMarkupKind::Bold => NorgInline::Bold { markup, attrs },
-                        InlineMarkupKind::Italic => NorgInline::Italic { markup, attrs },
-                        InlineMarkupKind::Underline => NorgInline::Underline { markup, attrs },
-                        InlineMarkupKind::Strikethrough => NorgInline::Strikethrough { markup, attrs },
-                        _ => unreachable!(),
-                    })
-                } else {
-                    Some(NorgInline::Special(ch.to_string()))
-                }
+            if closing.kind.is_verbatim() {
+                return InlineStep::Node(special());
             }
-            Special('\\') => {
-                let next_ch = self.char_at(tk.span.end);
-                if next_ch.is_punctuation() {
-                    self.pos = tk.span.end + next_ch.len_utf8();
-                    Some(NorgInline::Escape(next_ch))
-                } else if next_ch == '\n' {
-                    self.pos = tk.span.end;
-                    Some(NorgInline::HardBreak)
-                } else {
-                    Some(NorgInline::Special('\\'.to_string()))
-                }
+        }
+        let inline = match ch {
+            '[' => self.anchor(start),
+            '{' => self.link(start),
+            '\\' => self.backslash(start),
+            _ => InlineMarkupKind::from_modifier(ch).and_then(|kind| self.attached(start, kind)),
+        };
+        InlineStep::Node(inline.unwrap_or_else(|| {
+            self.pos = tk.span.end;
+            special()
+        }))
+    }
+
+    /// Attached modifier opened at `start`, `None` when it isn't opened or closed.
+    fn attached<S: SourceText<'src>>(
+        &mut self,
+        start: usize,
+        kind: InlineMarkupKind,
+    ) -> Option<NorgInline<S>> {
+        let modifier = kind.closing_char();
+        let count = self.count_repeated(start, modifier);
+        if count > 1 {
+            // repeated modifiers are plain punctuation
+            self.pos = start + count;
+            return Some(NorgInline::Special {
+                text: self.source[start..self.pos].into(),
+                range: self.lines.range(start..self.pos),
+            });
+        }
+        if !self.can_open(start, kind) {
+            return None;
+        }
+        let free_form = self.char_at(start + 1) == Some('|');
+        self.pos = start + if free_form { 2 } else { 1 };
+        let markup = if free_form && kind.is_verbatim() {
+            // free-form verbatim content is kept raw, see `verbatim_markup` in `crate::parser`
+            let content_start = self.pos;
+            let content_len = self.source[content_start..self.end].find(&format!("|{modifier}"))?;
+            let content = content_start..content_start + content_len;
+            self.pos = content.end + 2;
+            if content.is_empty() {
+                vec![]
+            } else {
+                vec![NorgInline::Text {
+                    text: self.source[content.clone()].into(),
+                    range: self.lines.range(content),
+                }]
+            }
+        } else {
+            self.inline_stack.push(kind);
+            let markup = self.parse_markup(Some(Closing { kind, free_form }));
+            self.inline_stack.pop();
+            markup?
+        };
+        let attrs = self.parse_inline_attrs();
+        let range = self.lines.range(start..self.pos);
+        use NorgInline::*;
+        Some(match kind {
+            InlineMarkupKind::Bold => Bold {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Italic => Italic {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Underline => Underline {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Strikethrough => Strikethrough {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Spoiler => Spoiler {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Superscript => Superscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Subscript => Subscript {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Verbatim => Verbatim {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::InlineMath => InlineMath {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Variable => Variable {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::NullModifier => NullModifier {
+                markup,
+                attrs,
+                free_form,
+                range,
+            },
+            InlineMarkupKind::Bracket => unreachable!("brackets aren't attached modifiers"),
+        })
+    }
+
+    /// `[markup]{target}(attrs)`, target and attributes being optional
+    fn anchor<S: SourceText<'src>>(&mut self, start: usize) -> Option<NorgInline<S>> {
+        self.pos = start;
+        let markup = self.parse_bracket()?;
+        let target = self.parse_target();
+        let attrs = self.parse_inline_attrs();
+        Some(NorgInline::Anchor {
+            target,
+            id: slugify(&markup_text(&markup)),
+            markup,
+            attrs,
+            range: self.lines.range(start..self.pos),
+        })
+    }
+
+    /// `{target}[markup](attrs)`, markup and attributes being optional
+    fn link<S: SourceText<'src>>(&mut self, start: usize) -> Option<NorgInline<S>> {
+        self.pos = start;
+        let target = self.parse_target()?;
+        let markup = self.parse_bracket();
+        let attrs = self.parse_inline_attrs();
+        Some(NorgInline::Link {
+            target,
+            markup,
+            attrs,
+            range: self.lines.range(start..self.pos),
+        })
+    }
+
+    /// Escape sequence, hard break or `\name(args)[markup]` inline macro
+    fn backslash<S: SourceText<'src>>(&mut self, start: usize) -> Option<NorgInline<S>> {
+        let next = self.char_at(start + 1)?;
+        if is_special(next) {
+            self.pos = start + 1 + next.len_utf8();
+            return Some(NorgInline::Escape {
+                character: next,
+                range: self.lines.range(start..self.pos),
+            });
+        }
+        if matches!(next, '\n' | '\r') {
+            self.pos = self.newline_end(start + 1);
+            return Some(NorgInline::HardBreak {
+                range: self.lines.range(start..self.pos),
+            });
+        }
+        if !next.is_alphanumeric() {
+            return None;
+        }
+        self.pos = start + 1;
+        while let Some(ch) = self
+            .current_char()
+            .filter(|&ch| ch.is_alphanumeric() || ch == '-' || ch == '_')
+        {
+            self.pos += ch.len_utf8();
+        }
+        let name = self.source[start + 1..self.pos].into();
+        let mut attrs = None;
+        if self.current_char() == Some('(') {
+            if let Some(len) = self.source[self.pos..self.end].find(')') {
+                let args = &self.source[self.pos + 1..self.pos + len];
+                attrs = Some(
+                    args.split(';')
+                        .map(str::trim)
+                        .filter(|arg| !arg.is_empty())
+                        .map(S::from)
+                        .collect(),
+                );
+                self.pos += len + 1;
             }
-            Special(ch) => Some(NorgInline::Special(ch.to_string())),
         }
+        let markup = self.parse_bracket();
+        Some(NorgInline::Macro {
+            name,
+            markup,
+            attrs,
+            range: self.lines.range(start..self.pos),
+        })
     }
 
-    fn char_at(&self, pos: usize) -> char {
-        if pos >= self.source.len() {
-            return '\0';
+    /// `[markup]` at current position
+    fn parse_bracket<S: SourceText<'src>>(&mut self) -> Option<Vec<NorgInline<S>>> {
+        if self.current_char() != Some('[') {
+            return None;
         }
-        self.source[pos..].chars().next().unwrap_or('\0')
+        let start = self.pos;
+        self.pos += 1;
+        self.inline_stack.push(InlineMarkupKind::Bracket);
+        let markup = self.parse_markup(Some(Closing {
+            kind: InlineMarkupKind::Bracket,
+            free_form: false,
+        }));
+        self.inline_stack.pop();
+        if markup.is_none() {
+            self.pos = start;
+        }
+        markup
     }
 
-    // NOTE: as Parser needs to backtrace a lot, it would be better remove
-    // `self.pos` state entirely and pass it to common methods like:
-    // - `lex_common(pos: usize)`
-    // - `parse_block(pos: usize)`
-    fn current_char(&self) -> char {
+    /// `{target}` at current position
+    fn parse_target(&mut self) -> Option<NorgLinkTarget> {
+        if self.current_char() != Some('{') {
+            return None;
+        }
+        let len = self.source[self.pos..self.end].find('}')?;
+        let target = NorgLinkTarget::parse(&self.source[self.pos + 1..self.pos + len]);
+        self.pos += len + 1;
+        Some(target)
+    }
+
+    /// `(attrs)` right after inline markup
+    fn parse_inline_attrs(&mut self) -> Vec<Attribute> {
+        if self.current_char() != Some('(') {
+            return vec![];
+        }
+        let Some(len) = self.source[self.pos..self.end].find(')') else {
+            return vec![];
+        };
+        let attrs = parse_attribute_list(&self.source[self.pos + 1..self.pos + len]);
+        self.pos += len + 1;
+        attrs
+    }
+
+    fn is_closing(&self, pos: usize, closing: Closing) -> bool {
+        let modifier = closing.kind.closing_char();
+        if closing.free_form {
+            self.char_at(pos) == Some('|') && self.char_at(pos + 1) == Some(modifier)
+        } else {
+            self.char_at(pos) == Some(modifier)
+                && (closing.kind == InlineMarkupKind::Bracket || self.can_close(pos))
+        }
+    }
+
+    /// opening modifier has to be followed by non-whitespace and not be glued to a word
+    fn can_open(&self, pos: usize, kind: InlineMarkupKind) -> bool {
+        let next = self.char_at(pos + 1);
+        let prev = self.char_before(pos);
+        !self.inline_stack.contains(&kind)
+            && next.is_some_and(|next| !next.is_whitespace())
+            && prev.is_none_or(|prev| prev.is_whitespace() || is_special(prev))
+    }
+
+    /// closing modifier has to follow non-whitespace and not be glued to a word
+    fn can_close(&self, pos: usize) -> bool {
+        let next = self.char_at(pos + 1);
+        let prev = self.char_before(pos);
+        !prev.is_some_and(char::is_whitespace)
+            && next.is_none_or(|next| next.is_whitespace() || is_special(next))
+    }
+
+    /// character at `pos`, `None` at the end of scanned text
+    fn char_at(&self, pos: usize) -> Option<char> {
+        if pos >= self.end {
+            return None;
+        }
+        self.source[pos..].chars().next()
+    }
+
+    /// character right before `pos`, `None` at the start of source
+    fn char_before(&self, pos: usize) -> Option<char> {
+        self.source[..pos].chars().next_back()
+    }
+
+    fn current_char(&self) -> Option<char> {
         self.char_at(self.pos)
     }
 
     fn lex_common_at(&self, start: usize) -> Mark<CommonToken> {
-        let first = self.char_at(start);
+        let Some(first) = self.char_at(start) else {
+            return Mark::new(CommonToken::Eof, start..start);
+        };
         match first {
-            ' ' => {
+            ' ' | '\t' => {
                 let mut pos = start;
-                loop {
-                    let next = self.char_at(pos);
-                    if next != ' ' {
-                        break;
-                    }
-                    pos += next.len_utf8();
+                while matches!(self.char_at(pos), Some(' ' | '\t')) {
+                    pos += 1;
                 }
-                if self.char_at(pos) == '\n' {
-                    pos += '\n'.len_utf8();
-                    Mark::new(CommonToken::Newline, start..pos)
-                } else {
-                    Mark::new(CommonToken::Space, start..pos)
+                match self.char_at(pos) {
+                    Some('\n' | '\r') => {
+                        Mark::new(CommonToken::Newline, start..self.newline_end(pos))
+                    }
+                    _ => Mark::new(CommonToken::Space, start..pos),
                 }
             }
-            '\r' => {
-                let mut pos = start + '\r'.len_utf8();
-                if self.char_at(pos) == '\n' {
-                    pos += '\n'.len_utf8();
-                }
-                Mark::new(CommonToken::Newline, start..pos)
-            },
-            '\n' => Mark::new(CommonToken::Newline, start..(start + '\n'.len_utf8())),
-            '\0' => Mark::new(CommonToken::Eof, start..start),
-            ch if ch.is_punctuation() => Mark::new(
-                CommonToken::Special(ch),
-                start..(start + ch.len_utf8()),
-            ),
+            '\r' | '\n' => Mark::new(CommonToken::Newline, start..self.newline_end(start)),
+            ch if is_special(ch) => {
+                Mark::new(CommonToken::Special(ch), start..start + ch.len_utf8())
+            }
             _ => {
                 let mut pos = start;
-                loop {
-                    let next = self.char_at(pos);
-                    if next.is_whitespace() || next.is_punctuation() || next == '\0' {
-                        break;
-                    }
+                while let Some(next) = self
+                    .char_at(pos)
+                    .filter(|&next| !next.is_whitespace() && !is_special(next))
+                {
                     pos += next.len_utf8();
                 }
-                assert_ne!(start, pos);
                 Mark::new(CommonToken::Text, start..pos)
             }
         }
     }
-    fn lex_line(&self, from: usize) -> Range<usize> {
-        let mut to = from;
-        loop {
-            let next = self.char_at(to);
-            if next == '\0' {
-                break;
-            }
-            to += next.len_utf8();
-            if next == '\n' || next == '\0' {
-                break;
-            }
+
+    /// end of `\n`, `\r\n` or `\r` at `pos`
+    fn newline_end(&self, pos: usize) -> usize {
+        match (self.char_at(pos), self.char_at(pos + 1)) {
+            (Some('\r'), Some('\n')) => pos + 2,
+            _ => pos + 1,
         }
+    }
+
+    /// line from `from`, including its newline
+    fn lex_line(&self, from: usize) -> Span {
+        let to = self.source[from..self.end]
+            .find('\n')
+            .map_or(self.end, |newline| from + newline + 1);
         from..to
     }
 }
 
+/// punctuation or symbol, which can't be part of a word
+pub(crate) fn is_special(ch: char) -> bool {
+    ch.is_ascii_punctuation() || ch.is_punctuation() || ch.is_symbol()
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub enum AtomBlock {
+pub(crate) enum AtomBlock {
     BlankLine,
     Paragraph,
     Heading {
         level: usize,
-        inline: Option<Range<usize>>,
+        /// `(attrs)` span, with parentheses
+        attrs: Option<Span>,
+        title: Option<Span>,
     },
     InfirmTag {
-        ident: Range<usize>,
+        ident: Span,
+        params: Option<Span>,
     },
     CarryoverTag {
-        ident: Range<usize>,
+        ident: Span,
+        params: Option<Span>,
+    },
+    /// `#(attrs)`, span of attributes without parentheses
+    CarryoverAttributes {
+        attrs: Span,
     },
     RangedTag {
-        ident: Range<usize>,
-        content: Vec<Range<usize>>,
+        ident: Span,
+        params: Option<Span>,
+        content: Vec<Span>,
+        /// whether `@end` was found
+        closed: bool,
+    },
+    HorizontalLine,
+    /// `$ title` or `^ title`, or `$$ title` and `^^ title` opening ranged one
+    DetachedTitle {
+        kind: Detached,
+        ranged: bool,
+        title: Span,
+    },
+    /// `$$` or `^^` closing ranged definition or footnote
+    RangedEnd {
+        kind: Detached,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Detached {
+    Definition,
+    Footnote,
+}
+
+impl Detached {
+    fn ranged_node(self) -> &'static str {
+        match self {
+            Self::Definition => "ranged_definition",
+            Self::Footnote => "ranged_footnote",
+        }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum Indent {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Indent {
     Unordered,
     Ordered,
     Quote,
-    Null,
 }
 
 impl TryFrom<char> for Indent {
@@ -604,20 +1473,19 @@ impl TryFrom<char> for Indent {
             '-' => Ok(Self::Unordered),
             '~' => Ok(Self::Ordered),
             '>' => Ok(Self::Quote),
-            '/' => Ok(Self::Null),
             _ => Err(()),
         }
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct Mark<K> {
+pub(crate) struct Mark<K> {
     kind: K,
-    span: Range<usize>,
+    span: Span,
 }
 
 impl<K> Mark<K> {
-    fn new(kind: K, span: Range<usize>) -> Self {
+    fn new(kind: K, span: Span) -> Self {
         Self { kind, span }
     }
     /// len_utf8
@@ -639,3 +1507,116 @@ enum CommonToken {
     Text,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_blocks() {
+        let ast = parse("* (x) Title\n#(html.class a)\n- one\n-- nested\n- *two*\n@code\nx\n");
+        let NorgBlock::Section {
+            heading: Some(heading),
+            contents,
+            ..
+        } = &ast.blocks[0]
+        else {
+            panic!("expected section, got {:?}", ast.blocks[0]);
+        };
+        assert_eq!(heading.id, "title");
+        assert!(heading.todo.is_some());
+        let NorgBlock::UnorderedList { attrs, items, .. } = &contents[0] else {
+            panic!("expected list, got {:?}", contents[0]);
+        };
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(items.len(), 2);
+        assert!(matches!(
+            items[0].contents[1],
+            NorgBlock::UnorderedList { .. }
+        ));
+        assert!(matches!(
+            &items[1].contents[0],
+            NorgBlock::Paragraph { inlines, .. } if matches!(inlines[0], NorgInline::Bold { .. })
+        ));
+        assert!(matches!(
+            contents[1],
+            NorgBlock::RangedTag { name: "code", .. }
+        ));
+        assert_eq!(ast.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_inlines() {
+        let ast = parse("{a.com}[*link*] [b]{:file:} \\img(x; y) `|*raw*|`(a b)\n");
+        let NorgBlock::Paragraph { inlines, .. } = &ast.blocks[0] else {
+            panic!("expected paragraph, got {:?}", ast.blocks[0]);
+        };
+        assert!(
+            matches!(&inlines[0], NorgInline::Link { markup: Some(markup), .. } if matches!(markup[0], NorgInline::Bold { .. }))
+        );
+        assert!(matches!(&inlines[2], NorgInline::Anchor { target: Some(_), id, .. } if id == "b"));
+        assert!(
+            matches!(&inlines[4], NorgInline::Macro { name: "img", attrs: Some(attrs), .. } if *attrs == ["x", "y"])
+        );
+        let NorgInline::Verbatim {
+            markup,
+            attrs,
+            free_form: true,
+            ..
+        } = &inlines[6]
+        else {
+            panic!("expected verbatim, got {:?}", inlines[6]);
+        };
+        assert!(matches!(
+            markup[..],
+            [NorgInline::Text { text: "*raw*", .. }]
+        ));
+        assert_eq!(attrs.len(), 1);
+        assert!(ast.anchors.contains_key("b"));
+    }
+
+    #[test]
+    fn test_nul_is_text() {
+        let ast = parse("a\0b\n\0\n");
+        let [NorgBlock::Paragraph { inlines, .. }] = &ast.blocks[..] else {
+            panic!("expected paragraph, got {:?}", ast.blocks);
+        };
+        assert!(matches!(
+            inlines[..],
+            [
+                NorgInline::Text { text: "a\0b", .. },
+                NorgInline::SoftBreak { .. },
+                NorgInline::Text { text: "\0", .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_footnote_ids() {
+        let ast = parse("^ Note\none\n\n^ Note\ntwo\n");
+        let ids: Vec<_> = ast
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                NorgBlock::Footnote { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, ["note", "note-1"]);
+    }
+
+    #[test]
+    fn test_attrs_at_end_of_input() {
+        let ast = parse("* (x)");
+        let NorgBlock::Section {
+            heading: Some(heading),
+            ..
+        } = &ast.blocks[0]
+        else {
+            panic!("expected section, got {:?}", ast.blocks[0]);
+        };
+        assert!(heading.todo.is_some());
+        assert!(heading.inlines.is_empty());
+        // carryover attributes with nothing to apply to
+        assert!(parse("#(x)").blocks.is_empty());
+    }
+}
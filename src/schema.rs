@@ -0,0 +1,75 @@
+//! JSON Schema and TypeScript definitions of the serialized [`crate::parser::NorgAST`].
+//!
+//! Generated copies are shipped in `bindings/js/ast`, and `tests/schema.rs` fails when they
+//! drift from the rust types.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use ts_rs::TS;
+
+use crate::{
+    block::{Heading, ListItem, NorgBlock, TableCell, TableRow, Tag, Todo, TodoStatus},
+    diagnostic::{Diagnostic, Severity},
+    inline::{Attribute, NorgInline},
+    parser::{AnchorDefinitionNode, AnchorIssue, Point, Range, AST_VERSION},
+    target::{NorgLinkAppTarget, NorgLinkLocalTarget, NorgLinkScope, NorgLinkTarget},
+};
+
+/// parsed norg document, as serialized by norg-rs
+#[derive(JsonSchema, TS)]
+#[schemars(rename = "NorgAST")]
+#[ts(rename = "NorgAST")]
+#[allow(dead_code)]
+struct SerializedAst {
+    /// format version, bumped on every incompatible change
+    #[schemars(extend("const" = AST_VERSION))]
+    version: u32,
+    blocks: Vec<NorgBlock>,
+    /// anchor definitions by their id
+    anchors: BTreeMap<String, AnchorDefinitionNode>,
+    /// ordered by position of the offending definition or reference
+    anchor_issues: Vec<AnchorIssue>,
+    /// problems found while parsing, ordered by position
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// JSON Schema of the serialized AST
+pub fn json_schema() -> schemars::Schema {
+    schemars::schema_for!(SerializedAst)
+}
+
+/// TypeScript declarations of the serialized AST and every type in it
+pub fn typescript() -> String {
+    let decls = [
+        SerializedAst::decl(),
+        <NorgBlock>::decl(),
+        <Heading>::decl(),
+        <ListItem>::decl(),
+        Todo::decl(),
+        TodoStatus::decl(),
+        <TableRow>::decl(),
+        <TableCell>::decl(),
+        <Tag>::decl(),
+        <NorgInline>::decl(),
+        Attribute::decl(),
+        NorgLinkTarget::decl(),
+        NorgLinkLocalTarget::decl(),
+        NorgLinkAppTarget::decl(),
+        NorgLinkScope::decl(),
+        AnchorDefinitionNode::decl(),
+        AnchorIssue::decl(),
+        Diagnostic::decl(),
+        Severity::decl(),
+        Range::decl(),
+        Point::decl(),
+    ];
+    let mut out =
+        String::from("// generated from norg-rs types by `tests/schema.rs`, don't edit\n");
+    for decl in decls {
+        out.push_str("\nexport ");
+        out.push_str(&decl);
+        out.push('\n');
+    }
+    out
+}
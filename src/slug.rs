@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use crate::inline::NorgInline;
+
+/// Turn text into a URL fragment: lowercase alphanumeric words joined with `-`.
+///
+/// `"My *Heading*: v2"` becomes `"my-heading-v2"`.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    let mut separated = false;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if separated && !slug.is_empty() {
+                slug.push('-');
+            }
+            separated = false;
+            slug.extend(c.to_lowercase());
+        } else {
+            separated = true;
+        }
+    }
+    slug
+}
+
+/// Plain text of markup, as used for slugs. Comments are left out.
+pub fn markup_text<S: AsRef<str>>(markup: &[NorgInline<S>]) -> String {
+    use NorgInline::*;
+    let mut text = String::new();
+    for inline in markup {
+        match inline {
+            Text { text: t, .. } | Special { text: t, .. } => text.push_str(t.as_ref()),
+            Escape { character, .. } => text.push(*character),
+            Whitespace { .. } | SoftBreak { .. } | HardBreak { .. } => text.push(' '),
+            Bold { markup, .. }
+            | Italic { markup, .. }
+            | Underline { markup, .. }
+            | Strikethrough { markup, .. }
+            | Verbatim { markup, .. }
+            | Spoiler { markup, .. }
+            | Superscript { markup, .. }
+            | Subscript { markup, .. }
+            | InlineMath { markup, .. }
+            | Variable { markup, .. }
+            | Anchor { markup, .. } => text.push_str(&markup_text(markup)),
+            Macro { markup, .. } | Link { markup, .. } => {
+                if let Some(markup) = markup {
+                    text.push_str(&markup_text(markup));
+                }
+            }
+            NullModifier { .. } => {}
+        }
+    }
+    text
+}
+
+/// Hands out document-unique ids. Repeated slugs get `-1`, `-2`, ... suffixes in order of
+/// appearance.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    used: HashSet<String>,
+}
+
+impl Slugger {
+    pub fn unique(&mut self, text: &str) -> String {
+        let mut slug = slugify(text);
+        if slug.is_empty() {
+            slug = String::from("section");
+        }
+        let mut id = slug.clone();
+        let mut count = 0;
+        while self.used.contains(&id) {
+            count += 1;
+            id = format!("{slug}-{count}");
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("My *Heading*: v2"), "my-heading-v2");
+        assert_eq!(slugify("  Ünïcode  héading "), "ünïcode-héading");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn test_slugger_dedup() {
+        let mut slugger = Slugger::default();
+        assert_eq!(slugger.unique("Intro"), "intro");
+        assert_eq!(slugger.unique("intro"), "intro-1");
+        assert_eq!(slugger.unique("Intro 1"), "intro-1-1");
+        assert_eq!(slugger.unique("Intro"), "intro-2");
+        assert_eq!(slugger.unique("???"), "section");
+    }
+}
@@ -1,18 +1,25 @@
 use std::path::PathBuf;
 
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum NorgLinkTarget {
     Local(NorgLinkLocalTarget),
     App(NorgLinkAppTarget),
+    /// target text that couldn't be parsed, kept as written
+    Raw(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum NorgLinkLocalTarget {
-    Raw(String),
+    Uri(String),
     Scope(Vec<NorgLinkScope>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub struct NorgLinkAppTarget {
     pub workspace: Option<String>,
     pub path: PathBuf,
@@ -21,18 +28,120 @@ pub struct NorgLinkAppTarget {
 
 pub type NorgMarkup = String;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema, ts_rs::TS))]
 pub enum NorgLinkScope {
     Heading(u16, NorgMarkup),
     WikiHeading(NorgMarkup),
+    Footnote(NorgMarkup),
+}
+
+impl NorgLinkTarget {
+    /// Parse link target text, following the same syntax as `norg/parse/target` in stdlib.
+    /// Text that doesn't fit the syntax is kept as [`NorgLinkTarget::Raw`].
+    pub fn parse(text: &str) -> Self {
+        Self::try_parse(text).unwrap_or_else(|| Self::Raw(text.to_string()))
+    }
+
+    fn try_parse(text: &str) -> Option<Self> {
+        let text = text.trim_start();
+        if text.is_empty() {
+            return None;
+        }
+        let mut parts = text.split(':').map(str::trim);
+        if text.starts_with(':') {
+            // skip empty text before the leading separator
+            parts.next();
+            let mut path = parts.next()?;
+            let mut workspace = None;
+            if let Some(name) = path.strip_prefix('$') {
+                workspace = Some(name.to_string());
+                path = parts.next()?;
+            }
+            if path.is_empty() || workspace.as_ref().is_some_and(String::is_empty) {
+                return None;
+            }
+            return Some(Self::App(NorgLinkAppTarget {
+                workspace,
+                path: PathBuf::from(path),
+                scopes: parse_scopes(parts)?,
+            }));
+        }
+        match parse_scopes(parts) {
+            Some(scopes) if !scopes.is_empty() => Some(Self::Local(NorgLinkLocalTarget::Scope(scopes))),
+            _ => Some(Self::Local(NorgLinkLocalTarget::Uri(text.to_string()))),
+        }
+    }
+}
+
+/// Parse `:`-separated scopes. Empty scopes are skipped.
+fn parse_scopes<'a>(parts: impl Iterator<Item = &'a str>) -> Option<Vec<NorgLinkScope>> {
+    parts
+        .filter(|part| !part.is_empty())
+        .map(NorgLinkScope::parse)
+        .collect()
+}
+
+impl NorgLinkScope {
+    fn parse(text: &str) -> Option<Self> {
+        if let Some(title) = text.strip_prefix('?') {
+            let title = title.strip_prefix(char::is_whitespace)?.trim_start();
+            (!title.is_empty()).then(|| Self::WikiHeading(title.to_string()))
+        } else if let Some(title) = text.strip_prefix('^') {
+            let title = title.strip_prefix(char::is_whitespace)?.trim_start();
+            (!title.is_empty()).then(|| Self::Footnote(title.to_string()))
+        } else {
+            let title = text.trim_start_matches('*');
+            let level = text.len() - title.len();
+            let title = title.trim_start();
+            (level > 0 && !title.is_empty()).then(|| Self::Heading(level as u16, title.to_string()))
+        }
+    }
 }
 
+/// Writes target back in the syntax [`NorgLinkTarget::parse`] reads.
+impl std::fmt::Display for NorgLinkTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local(NorgLinkLocalTarget::Uri(uri)) => f.write_str(uri),
+            Self::Local(NorgLinkLocalTarget::Scope(scopes)) => write_scopes(f, scopes),
+            Self::App(app) => {
+                f.write_str(":")?;
+                if let Some(workspace) = &app.workspace {
+                    write!(f, "${workspace}:")?;
+                }
+                write!(f, "{}:", app.path.display())?;
+                write_scopes(f, &app.scopes)
+            }
+            Self::Raw(text) => f.write_str(text),
+        }
+    }
+}
+
+fn write_scopes(f: &mut std::fmt::Formatter<'_>, scopes: &[NorgLinkScope]) -> std::fmt::Result {
+    for (i, scope) in scopes.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" : ")?;
+        }
+        match scope {
+            NorgLinkScope::Heading(level, title) => {
+                write!(f, "{} {title}", "*".repeat(*level as usize))?
+            }
+            NorgLinkScope::WikiHeading(title) => write!(f, "? {title}")?,
+            NorgLinkScope::Footnote(title) => write!(f, "^ {title}")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "janet")]
 impl Into<janetrs::Janet> for NorgLinkTarget {
     fn into(self) -> janetrs::Janet {
         janetrs::Janet::tuple(self.into())
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<janetrs::JanetTuple<'_>> for NorgLinkTarget {
     fn into(self) -> janetrs::JanetTuple<'static> {
         match self {
@@ -44,18 +153,28 @@ impl Into<janetrs::JanetTuple<'_>> for NorgLinkTarget {
                 janetrs::JanetKeyword::new("app"),
                 janetrs::Janet::structs(app.into()),
             ],
+            Self::Raw(raw) => janetrs::tuple![
+                janetrs::JanetKeyword::new("raw"),
+                janetrs::Janet::string(raw.into()),
+            ],
         }
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::Janet> for NorgLinkTarget {
     type Error = janetrs::JanetConversionError;
 
     fn try_from(value: janetrs::Janet) -> Result<Self, Self::Error> {
+        // tags written in janet can give the target as plain text
+        if let Ok(text) = value.try_unwrap::<janetrs::JanetString>() {
+            return Ok(Self::parse(&text.to_str_lossy()));
+        }
         Self::try_from(value.try_unwrap::<janetrs::JanetTuple>()?)
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkTarget {
     type Error = janetrs::JanetConversionError;
 
@@ -67,26 +186,34 @@ impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkTarget {
         match kind.as_bytes() {
             b"local" => Ok(Self::Local(NorgLinkLocalTarget::try_from(*value)?)),
             b"app" => Ok(Self::App(NorgLinkAppTarget::try_from(*value)?)),
+            b"raw" => Ok(Self::Raw(
+                value
+                    .try_unwrap::<janetrs::JanetString>()?
+                    .to_str_lossy()
+                    .to_string(),
+            )),
             _ => todo!("error"),
         }
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<janetrs::JanetTuple<'_>> for NorgLinkLocalTarget {
     fn into(self) -> janetrs::JanetTuple<'static> {
         match self {
-            Self::Raw(raw) => janetrs::tuple![
-                janetrs::JanetKeyword::new("raw"),
-                janetrs::Janet::string(raw.into()),
+            Self::Uri(uri) => janetrs::tuple![
+                janetrs::JanetKeyword::new("uri"),
+                janetrs::Janet::string(uri.into()),
             ],
-            Self::Scope(_scopes) => janetrs::tuple![
+            Self::Scope(scopes) => janetrs::tuple![
                 janetrs::JanetKeyword::new("scopes"),
-                janetrs::tuple![],
+                janetrs::Janet::tuple(scopes.into_iter().collect()),
             ],
         }
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::Janet> for NorgLinkLocalTarget {
     type Error = janetrs::JanetConversionError;
 
@@ -95,6 +222,7 @@ impl TryFrom<janetrs::Janet> for NorgLinkLocalTarget {
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkLocalTarget {
     type Error = janetrs::JanetConversionError;
 
@@ -104,7 +232,7 @@ impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkLocalTarget {
         };
         let kind = local_kind.try_unwrap::<janetrs::JanetKeyword>()?;
         match kind.as_bytes() {
-            b"raw" => Ok(Self::Raw(
+            b"uri" => Ok(Self::Uri(
                 value
                     .try_unwrap::<janetrs::JanetString>()?
                     .to_str_lossy()
@@ -133,6 +261,7 @@ impl From<PathBuf> for NorgLinkAppTarget {
     }
 }
 
+#[cfg(feature = "janet")]
 impl Into<janetrs::JanetStruct<'_>> for NorgLinkAppTarget {
     fn into(self) -> janetrs::JanetStruct<'static> {
         janetrs::JanetStruct::builder(3)
@@ -142,17 +271,17 @@ impl Into<janetrs::JanetStruct<'_>> for NorgLinkAppTarget {
             })
             .put(
                 janetrs::JanetKeyword::new("path"),
-                janetrs::JanetString::from(self.path.to_str().unwrap()),
+                janetrs::JanetString::from(self.path.to_string_lossy().as_ref()),
             )
             .put(
                 janetrs::JanetKeyword::new("scopes"),
-                // TODO: pass scopes
-                janetrs::tuple![],
+                janetrs::Janet::tuple(self.scopes.into_iter().collect()),
             )
             .finalize()
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::Janet> for NorgLinkAppTarget {
     type Error = janetrs::JanetConversionError;
 
@@ -161,6 +290,7 @@ impl TryFrom<janetrs::Janet> for NorgLinkAppTarget {
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::JanetStruct<'_>> for NorgLinkAppTarget {
     type Error = janetrs::JanetConversionError;
 
@@ -195,6 +325,28 @@ impl TryFrom<janetrs::JanetStruct<'_>> for NorgLinkAppTarget {
     }
 }
 
+#[cfg(feature = "janet")]
+impl Into<janetrs::Janet> for NorgLinkScope {
+    fn into(self) -> janetrs::Janet {
+        janetrs::Janet::tuple(match self {
+            Self::Heading(level, title) => janetrs::tuple![
+                janetrs::JanetKeyword::new("heading"),
+                level as usize,
+                janetrs::Janet::string(title.into()),
+            ],
+            Self::WikiHeading(title) => janetrs::tuple![
+                janetrs::JanetKeyword::new("wiki"),
+                janetrs::Janet::string(title.into()),
+            ],
+            Self::Footnote(title) => janetrs::tuple![
+                janetrs::JanetKeyword::new("footnote"),
+                janetrs::Janet::string(title.into()),
+            ],
+        })
+    }
+}
+
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::Janet> for NorgLinkScope {
     type Error = janetrs::JanetConversionError;
 
@@ -203,6 +355,7 @@ impl TryFrom<janetrs::Janet> for NorgLinkScope {
     }
 }
 
+#[cfg(feature = "janet")]
 impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkScope {
     type Error = janetrs::JanetConversionError;
 
@@ -215,11 +368,81 @@ impl TryFrom<janetrs::JanetTuple<'_>> for NorgLinkScope {
         match kind.as_bytes() {
             b"heading" => Ok({
                 let level = values[0].try_unwrap::<f64>()? as u16;
-                let title = values[1].to_string();
+                let title = values[1]
+                    .try_unwrap::<janetrs::JanetString>()?
+                    .to_str_lossy()
+                    .to_string();
                 Self::Heading(level, title)
             }),
-            b"wiki" => todo!(),
+            b"wiki" => Ok(Self::WikiHeading(
+                values[0]
+                    .try_unwrap::<janetrs::JanetString>()?
+                    .to_str_lossy()
+                    .to_string(),
+            )),
+            b"footnote" => Ok(Self::Footnote(
+                values[0]
+                    .try_unwrap::<janetrs::JanetString>()?
+                    .to_str_lossy()
+                    .to_string(),
+            )),
             _ => todo!("error"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        use NorgLinkScope::*;
+        assert_eq!(
+            NorgLinkTarget::parse("https://example.com"),
+            NorgLinkTarget::Local(NorgLinkLocalTarget::Uri("https://example.com".to_string())),
+        );
+        assert_eq!(
+            NorgLinkTarget::parse("* heading : ** sub heading"),
+            NorgLinkTarget::Local(NorgLinkLocalTarget::Scope(vec![
+                Heading(1, "heading".to_string()),
+                Heading(2, "sub heading".to_string()),
+            ])),
+        );
+        assert_eq!(
+            NorgLinkTarget::parse(":$notes:path/to/file:? wiki"),
+            NorgLinkTarget::App(NorgLinkAppTarget {
+                workspace: Some("notes".to_string()),
+                path: PathBuf::from("path/to/file"),
+                scopes: vec![WikiHeading("wiki".to_string())],
+            }),
+        );
+        assert_eq!(
+            NorgLinkTarget::parse("^ note"),
+            NorgLinkTarget::Local(NorgLinkLocalTarget::Scope(vec![Footnote("note".to_string())])),
+        );
+        assert_eq!(
+            NorgLinkTarget::parse(":file"),
+            NorgLinkTarget::App(PathBuf::from("file").into()),
+        );
+        assert_eq!(
+            NorgLinkTarget::parse(":file:not a scope"),
+            NorgLinkTarget::Raw(":file:not a scope".to_string()),
+        );
+        assert_eq!(NorgLinkTarget::parse(""), NorgLinkTarget::Raw(String::new()));
+    }
+
+    #[test]
+    fn test_display_target() {
+        for text in [
+            "https://example.com",
+            "* a : ** b",
+            ":$notes:file:? wiki",
+            ":file:",
+            "^ note",
+        ] {
+            let target = NorgLinkTarget::parse(text);
+            assert_eq!(NorgLinkTarget::parse(&target.to_string()), target);
+        }
+    }
+}
@@ -0,0 +1,555 @@
+//! Write AST back to norg markup. Parsing the written text gives the same AST, ranges aside.
+//!
+//! Some ASTs can't be written as is: `Embed` blocks are skipped, blocks following a section
+//! are read back as part of it, and only the first block and nested lists of a list item stay
+//! in the item. Leading whitespace of continuation lines in a list item is written as
+//! indentation to the item text.
+
+use crate::{
+    block::{Heading, ListItem, NorgBlock, TableRow},
+    inline::{Attribute, NorgInline},
+    parser::NorgAST,
+    parser2::is_special,
+};
+
+pub fn to_norg(ast: &NorgAST) -> String {
+    blocks_to_norg(&ast.blocks)
+}
+
+pub fn blocks_to_norg(blocks: &[NorgBlock]) -> String {
+    let mut out = String::new();
+    write_blocks(&mut out, blocks);
+    out
+}
+
+pub fn inlines_to_norg(inlines: &[NorgInline]) -> String {
+    let mut out = String::new();
+    write_inlines(&mut out, inlines);
+    out
+}
+
+pub(crate) fn attrs_to_norg(attrs: &[Attribute]) -> String {
+    let mut out = String::new();
+    write_attrs(&mut out, attrs);
+    out
+}
+
+/// blocks separated by blank lines
+fn write_blocks(out: &mut String, blocks: &[NorgBlock]) {
+    #[cfg(feature = "janet")]
+    let blocks = blocks
+        .iter()
+        .filter(|block| !matches!(block, NorgBlock::Embed { .. }));
+    #[cfg(not(feature = "janet"))]
+    let blocks = blocks.iter();
+    for (i, block) in blocks.enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        write_block(out, block);
+    }
+}
+
+fn write_block(out: &mut String, block: &NorgBlock) {
+    use NorgBlock::*;
+    match block {
+        Section {
+            attrs,
+            level,
+            heading,
+            contents,
+            ..
+        } => {
+            write_carryover_attrs(out, attrs);
+            out.push_str(&"*".repeat(*level as usize));
+            if let Some(heading) = heading {
+                write_heading(out, heading);
+            }
+            out.push('\n');
+            if !contents.is_empty() {
+                out.push('\n');
+                write_blocks(out, contents);
+            }
+        }
+        Paragraph { attrs, inlines, .. } => write_paragraph(out, attrs, inlines, 0),
+        UnorderedList {
+            attrs,
+            level,
+            items,
+            ..
+        } => write_list(out, '-', attrs, *level, items),
+        OrderedList {
+            attrs,
+            level,
+            items,
+            ..
+        } => write_list(out, '~', attrs, *level, items),
+        Quote {
+            attrs,
+            level,
+            items,
+            ..
+        } => write_list(out, '>', attrs, *level, items),
+        InfirmTag {
+            attrs,
+            params,
+            name,
+            ..
+        } => {
+            write_carryover_attrs(out, attrs);
+            write_tag_line(out, '.', name, params);
+        }
+        CarryoverTag {
+            params,
+            name,
+            target,
+            ..
+        } => {
+            write_tag_line(out, '#', name, params);
+            write_block(out, target);
+        }
+        RangedTag {
+            attrs,
+            params,
+            name,
+            content,
+            ..
+        } => {
+            write_carryover_attrs(out, attrs);
+            write_tag_line(out, '@', name, params);
+            for line in content {
+                out.push_str(line);
+                if !line.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            out.push_str("@end\n");
+        }
+        #[cfg(feature = "janet")]
+        Embed { .. } => {}
+        HorizontalLine { attrs, .. } => {
+            write_carryover_attrs(out, attrs);
+            out.push_str("___\n");
+        }
+        Definition {
+            attrs,
+            title,
+            contents,
+            ..
+        } => {
+            write_carryover_attrs(out, attrs);
+            write_detached(out, '$', title, contents);
+        }
+        Footnote {
+            attrs,
+            title,
+            contents,
+            ..
+        } => {
+            write_carryover_attrs(out, attrs);
+            write_detached(out, '^', title, contents);
+        }
+        Table { attrs, rows, .. } => {
+            write_carryover_attrs(out, attrs);
+            write_table(out, rows);
+        }
+    }
+}
+
+/// heading after its `*` prefix
+fn write_heading(out: &mut String, heading: &Heading) {
+    if heading.attrs.is_empty() && heading.inlines.is_empty() {
+        return;
+    }
+    out.push(' ');
+    if !heading.attrs.is_empty() {
+        write_attrs(out, &heading.attrs);
+        if heading.inlines.is_empty() {
+            return;
+        }
+        out.push(' ');
+    }
+    write_inlines(out, &heading.inlines);
+}
+
+fn write_list(out: &mut String, prefix: char, attrs: &[Attribute], level: u16, items: &[ListItem]) {
+    write_carryover_attrs(out, attrs);
+    for item in items {
+        for tag in &item.tags {
+            write_tag_line(out, '#', &tag.name, &tag.params);
+        }
+        out.push_str(&prefix.to_string().repeat(level as usize));
+        out.push(' ');
+        if !item.attrs.is_empty() {
+            write_attrs(out, &item.attrs);
+        }
+        let nested = match item.contents.split_first() {
+            Some((first, rest)) if !is_list(first) => {
+                if !item.attrs.is_empty() {
+                    out.push(' ');
+                }
+                match first {
+                    NorgBlock::Paragraph { attrs, inlines, .. } => {
+                        write_paragraph(out, attrs, inlines, level as usize + 1)
+                    }
+                    first => write_block(out, first),
+                }
+                rest
+            }
+            _ => {
+                out.push('\n');
+                &item.contents[..]
+            }
+        };
+        for block in nested {
+            write_block(out, block);
+        }
+    }
+}
+
+/// paragraph with its continuation lines indented by `indent` spaces
+fn write_paragraph(out: &mut String, attrs: &[Attribute], inlines: &[NorgInline], indent: usize) {
+    write_carryover_attrs(out, attrs);
+    let mut inlines = inlines.iter().peekable();
+    while let Some(inline) = inlines.next() {
+        write_inline(out, inline);
+        let after_break = matches!(
+            inline,
+            NorgInline::SoftBreak { .. } | NorgInline::HardBreak { .. }
+        );
+        // indentation replaces the leading whitespace, lines without one stay unindented
+        if after_break
+            && indent > 0
+            && inlines
+                .next_if(|inline| matches!(inline, NorgInline::Whitespace { .. }))
+                .is_some()
+        {
+            out.push_str(&" ".repeat(indent));
+        }
+    }
+    out.push('\n');
+}
+
+fn is_list(block: &NorgBlock) -> bool {
+    matches!(
+        block,
+        NorgBlock::UnorderedList { .. } | NorgBlock::OrderedList { .. } | NorgBlock::Quote { .. }
+    )
+}
+
+/// single definition or footnote when its content is one paragraph, ranged one otherwise
+fn write_detached(out: &mut String, modifier: char, title: &[NorgInline], contents: &[NorgBlock]) {
+    match contents {
+        [paragraph @ NorgBlock::Paragraph { attrs, .. }] if attrs.is_empty() => {
+            out.push(modifier);
+            out.push(' ');
+            write_inlines(out, title);
+            out.push('\n');
+            write_block(out, paragraph);
+        }
+        _ => {
+            out.push_str(&format!("{modifier}{modifier} "));
+            write_inlines(out, title);
+            out.push('\n');
+            write_blocks(out, contents);
+            out.push_str(&format!("{modifier}{modifier}\n"));
+        }
+    }
+}
+
+fn write_table(out: &mut String, rows: &[TableRow]) {
+    out.push_str("@table\n");
+    let header_rows = rows.iter().take_while(|row| row.header).count();
+    for (i, row) in rows.iter().enumerate() {
+        out.push('|');
+        for cell in &row.cells {
+            out.push(' ');
+            if !cell.attrs.is_empty() {
+                write_attrs(out, &cell.attrs);
+                out.push(' ');
+            }
+            write_inlines(out, &cell.inlines);
+            out.push_str(" |");
+        }
+        out.push('\n');
+        if i + 1 == header_rows {
+            out.push('|');
+            for _ in &row.cells {
+                out.push_str("---|");
+            }
+            out.push('\n');
+        }
+    }
+    out.push_str("@end\n");
+}
+
+fn write_carryover_attrs(out: &mut String, attrs: &[Attribute]) {
+    if !attrs.is_empty() {
+        out.push('#');
+        write_attrs(out, attrs);
+        out.push('\n');
+    }
+}
+
+/// `(key value; key)`, with blank attribute written as `( )`
+fn write_attrs(out: &mut String, attrs: &[Attribute]) {
+    out.push('(');
+    for (i, attr) in attrs.iter().enumerate() {
+        if i > 0 {
+            out.push_str("; ");
+        }
+        match attr {
+            Attribute::Blank => out.push(' '),
+            Attribute::Key(key) => out.push_str(key),
+            Attribute::KeyValue(key, value) => {
+                out.push_str(key);
+                out.push(' ');
+                out.push_str(value);
+            }
+        }
+    }
+    out.push(')');
+}
+
+/// tag line like `.name params`, quoting params the way `parse_tag_params` reads them
+fn write_tag_line(out: &mut String, prefix: char, name: &str, params: &[String]) {
+    out.push(prefix);
+    out.push_str(name);
+    for param in params {
+        out.push(' ');
+        let quoted = param.is_empty() || param.contains(char::is_whitespace);
+        if quoted {
+            out.push('"');
+        }
+        for c in param.chars() {
+            if matches!(c, '"' | '\\') {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        if quoted {
+            out.push('"');
+        }
+    }
+    out.push('\n');
+}
+
+fn write_inlines(out: &mut String, inlines: &[NorgInline]) {
+    for inline in inlines {
+        write_inline(out, inline);
+    }
+}
+
+fn write_inline(out: &mut String, inline: &NorgInline) {
+    use NorgInline::*;
+    match inline {
+        Text { text, .. } => write_text(out, text),
+        Special { text, .. } => out.push_str(text),
+        Escape { character, .. } => {
+            out.push('\\');
+            out.push(*character);
+        }
+        Whitespace { .. } => out.push(' '),
+        SoftBreak { .. } => out.push('\n'),
+        HardBreak { .. } => out.push_str("\\\n"),
+        Bold {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '*', markup, attrs, *free_form),
+        Italic {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '/', markup, attrs, *free_form),
+        Underline {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '_', markup, attrs, *free_form),
+        Strikethrough {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '~', markup, attrs, *free_form),
+        Spoiler {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '!', markup, attrs, *free_form),
+        Superscript {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '^', markup, attrs, *free_form),
+        Subscript {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, ',', markup, attrs, *free_form),
+        Verbatim {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '`', markup, attrs, *free_form),
+        InlineMath {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '$', markup, attrs, *free_form),
+        Variable {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '&', markup, attrs, *free_form),
+        NullModifier {
+            markup,
+            attrs,
+            free_form,
+            ..
+        } => write_attached(out, '%', markup, attrs, *free_form),
+        Macro {
+            name,
+            markup,
+            attrs,
+            ..
+        } => {
+            out.push('\\');
+            out.push_str(name);
+            if let Some(attrs) = attrs {
+                out.push('(');
+                out.push_str(&attrs.join("; "));
+                out.push(')');
+            }
+            if let Some(markup) = markup {
+                write_bracket(out, markup);
+            }
+        }
+        Link {
+            target,
+            markup,
+            attrs,
+            ..
+        } => {
+            out.push_str(&format!("{{{target}}}"));
+            if let Some(markup) = markup {
+                write_bracket(out, markup);
+            }
+            write_inline_attrs(out, attrs);
+        }
+        Anchor {
+            target,
+            markup,
+            attrs,
+            ..
+        } => {
+            write_bracket(out, markup);
+            if let Some(target) = target {
+                out.push_str(&format!("{{{target}}}"));
+            }
+            write_inline_attrs(out, attrs);
+        }
+    }
+}
+
+fn write_attached(
+    out: &mut String,
+    modifier: char,
+    markup: &[NorgInline],
+    attrs: &[Attribute],
+    free_form: bool,
+) {
+    out.push(modifier);
+    if free_form {
+        out.push('|');
+    }
+    // verbatim content has no markup, so text in it is written unescaped
+    let verbatim = matches!(modifier, '`' | '$' | '&');
+    for inline in markup {
+        match inline {
+            NorgInline::Text { text, .. } if verbatim => out.push_str(text),
+            inline => write_inline(out, inline),
+        }
+    }
+    if free_form {
+        out.push('|');
+    }
+    out.push(modifier);
+    write_inline_attrs(out, attrs);
+}
+
+fn write_bracket(out: &mut String, markup: &[NorgInline]) {
+    out.push('[');
+    write_inlines(out, markup);
+    out.push(']');
+}
+
+fn write_inline_attrs(out: &mut String, attrs: &[Attribute]) {
+    if !attrs.is_empty() {
+        write_attrs(out, attrs);
+    }
+}
+
+/// Text escaping characters that would start markup: brackets and backslashes anywhere, and
+/// attached modifiers that could open or close markup. The text around a `Text` node is
+/// unknown, so its edges count as word boundaries.
+fn write_text(out: &mut String, text: &str) {
+    let chars: Vec<char> = text.chars().collect();
+    let boundary = |ch: Option<char>| ch.is_none_or(|ch| ch.is_whitespace() || is_special(ch));
+    for (i, &c) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1).copied();
+        let modifier = matches!(
+            c,
+            '*' | '/' | '_' | '~' | '!' | '^' | ',' | '`' | '$' | '&' | '%'
+        );
+        let opens = boundary(prev) && !next.is_some_and(char::is_whitespace);
+        let closes = boundary(next) && !prev.is_some_and(char::is_whitespace);
+        if matches!(c, '\\' | '[' | ']' | '{' | '}') || (modifier && (opens || closes)) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_norg() {
+        let text = "* (x) heading\n\n#(a b)\n- (html.class c) *bold*(d) {:file:}[link]\n-- \\gh(x)\n\n$$ term\nfirst\n\nsecond\n$$\n";
+        let ast = crate::parser::parse(text.as_bytes());
+        assert_eq!(to_norg(&ast), text);
+    }
+
+    #[test]
+    fn test_text_modifiers_roundtrip() {
+        let text = "a *b c* d";
+        let written = inlines_to_norg(&[NorgInline::Text {
+            text: text.to_string(),
+            range: Default::default(),
+        }]);
+        let ast = crate::parser::parse(written.as_bytes());
+        let [NorgBlock::Paragraph { inlines, .. }] = &ast.blocks[..] else {
+            panic!("expected a paragraph, got {:?}", ast.blocks);
+        };
+        assert!(
+            inlines
+                .iter()
+                .all(|inline| !matches!(inline, NorgInline::Bold { .. })),
+            "{written} parsed as {inlines:?}"
+        );
+        assert_eq!(crate::slug::markup_text(inlines), text);
+    }
+}
@@ -0,0 +1,132 @@
+//! Helpers shared by integration tests: fixture corpus and AST comparison.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use norg_rs::parser;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub path: String,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+/// `test.norg`, `test2.norg` and everything in `tests/fixtures`
+pub fn fixtures(root: &Path) -> Vec<PathBuf> {
+    let mut fixtures: Vec<_> = fs::read_dir(root.join("tests/fixtures"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "norg"))
+        .collect();
+    fixtures.sort();
+    fixtures.insert(0, root.join("test2.norg"));
+    fixtures.insert(0, root.join("test.norg"));
+    fixtures
+}
+
+/// path of the fixture relative to the crate root, with `/` separators
+pub fn fixture_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap()
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// AST as json with ranges left out
+pub fn ast_value(ast: &parser::NorgAST) -> Value {
+    normalise(ast_json(ast), false)
+}
+
+/// AST as json, ranges included
+#[allow(dead_code)] // only used by the conformance tests
+pub fn ast_value_with_ranges(ast: &parser::NorgAST) -> Value {
+    normalise(ast_json(ast), true)
+}
+
+fn ast_json(ast: &parser::NorgAST) -> Value {
+    let anchors: BTreeMap<_, _> = ast
+        .anchors
+        .iter()
+        .map(|(id, anchor)| (id, &anchor.target))
+        .collect();
+    json!({
+        "blocks": ast.blocks,
+        "anchors": anchors,
+        "anchor_issues": ast.anchor_issues,
+    })
+}
+
+/// Replace ranges with `null` unless `keep_ranges` is set, and flatten enum variants to objects
+/// with a `kind` field, so node paths don't depend on how variants are serialized.
+fn normalise(value: Value, keep_ranges: bool) -> Value {
+    match value {
+        Value::Object(map) if !keep_ranges && map.contains_key("start_point") => Value::Null,
+        Value::Object(map)
+            if map.len() == 1 && map.keys().all(|key| key.starts_with(char::is_uppercase)) =>
+        {
+            let (kind, inner) = map.into_iter().next().unwrap();
+            let mut node = match normalise(inner, keep_ranges) {
+                Value::Object(fields) => fields,
+                value => Map::from_iter([("value".to_string(), value)]),
+            };
+            node.insert("kind".to_string(), Value::String(kind));
+            Value::Object(node)
+        }
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, normalise(value, keep_ranges)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| normalise(value, keep_ranges))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+pub fn diff(path: String, expected: &Value, actual: &Value, divergences: &mut Vec<Divergence>) {
+    match (expected, actual) {
+        (Value::Object(left), Value::Object(right)) if left.get("kind") == right.get("kind") => {
+            for key in left
+                .keys()
+                .chain(right.keys().filter(|key| !left.contains_key(*key)))
+            {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                diff(
+                    path,
+                    left.get(key).unwrap_or(&Value::Null),
+                    right.get(key).unwrap_or(&Value::Null),
+                    divergences,
+                );
+            }
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            for i in 0..left.len().max(right.len()) {
+                diff(
+                    format!("{path}[{i}]"),
+                    left.get(i).unwrap_or(&Value::Null),
+                    right.get(i).unwrap_or(&Value::Null),
+                    divergences,
+                );
+            }
+        }
+        _ if expected == actual => {}
+        _ => divergences.push(Divergence {
+            path,
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }),
+    }
+}
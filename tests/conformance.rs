@@ -0,0 +1,83 @@
+//! Differential tests between the tree-sitter parser and `parser2`.
+//!
+//! Every fixture is parsed by both parsers and the resulting ASTs are compared, once with ranges
+//! stripped and once with them. The first diverging node path is reported. This is also the
+//! conformance suite for the unofficial norg spec: add a fixture for every construct both parsers
+//! should agree on.
+#![cfg(feature = "tree-sitter")]
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{ast_value, ast_value_with_ranges, diff, fixture_name, fixtures};
+use norg_rs::{
+    parser::{NorgAST, NorgParser},
+    parser2,
+};
+use serde_json::Value;
+
+/// Known differences as `(fixture, node path, reason)`. Divergences at or below the path are
+/// accepted. An entry that matches nothing fails the suite so it gets removed once fixed.
+const EXPECTED_DIFFERENCES: &[(&str, &str, &str)] = &[];
+
+/// Known differences that only show up once ranges are compared, in the same form as
+/// [`EXPECTED_DIFFERENCES`]
+const EXPECTED_SPAN_DIFFERENCES: &[(&str, &str, &str)] = &[];
+
+#[test]
+fn test_parsers_agree() {
+    assert_parsers_agree(ast_value, EXPECTED_DIFFERENCES);
+}
+
+#[test]
+fn test_parsers_agree_on_ranges() {
+    assert_parsers_agree(
+        ast_value_with_ranges,
+        &[EXPECTED_DIFFERENCES, EXPECTED_SPAN_DIFFERENCES].concat(),
+    );
+}
+
+fn assert_parsers_agree(value: fn(&NorgAST) -> Value, expected_differences: &[(&str, &str, &str)]) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut used = vec![false; expected_differences.len()];
+    let mut failures = vec![];
+    let mut parser = NorgParser::tree_sitter().unwrap();
+    for path in fixtures(root) {
+        let name = fixture_name(root, &path);
+        let text = fs::read_to_string(&path).unwrap();
+        let expected = value(&parser.parse(text.as_bytes()).unwrap());
+        let actual = value(&parser2::parse(&text).into_owned());
+        let mut divergences = vec![];
+        diff(String::new(), &expected, &actual, &mut divergences);
+        for divergence in divergences {
+            match expected_differences
+                .iter()
+                .position(|&(fixture, prefix, _)| {
+                    fixture == name && is_under(&divergence.path, prefix)
+                }) {
+                Some(i) => used[i] = true,
+                None => {
+                    failures.push(format!(
+                        "{name}: first divergence at `{}`\n  parser:  {}\n  parser2: {}",
+                        divergence.path, divergence.expected, divergence.actual
+                    ));
+                    break;
+                }
+            }
+        }
+    }
+    for (&(fixture, prefix, reason), used) in expected_differences.iter().zip(used) {
+        if !used {
+            failures.push(format!(
+                "{fixture}: expected difference at `{prefix}` ({reason}) is gone, remove it"
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+}
@@ -0,0 +1,64 @@
+//! Writing a parsed fixture back to norg and parsing it again gives the same AST, and
+//! formatting a fixture twice gives the same text.
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::{ast_value, diff, fixture_name, fixtures};
+use norg_rs::{
+    format::{format, FormatError, FormatOptions},
+    parser, writer,
+};
+
+#[test]
+fn test_roundtrip() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = vec![];
+    for path in fixtures(root) {
+        let text = fs::read_to_string(&path).unwrap();
+        let ast = parser::parse(text.as_bytes());
+        let written = writer::to_norg(&ast);
+        let mut divergences = vec![];
+        diff(
+            String::new(),
+            &ast_value(&ast),
+            &ast_value(&parser::parse(written.as_bytes())),
+            &mut divergences,
+        );
+        if let Some(divergence) = divergences.first() {
+            failures.push(format!(
+                "{}: first divergence at `{}`\n  parsed:  {}\n  written: {}\n{written}",
+                fixture_name(root, &path),
+                divergence.path,
+                divergence.expected,
+                divergence.actual
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_format_idempotent() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = vec![];
+    for path in fixtures(root) {
+        let text = fs::read_to_string(&path).unwrap();
+        for options in [FormatOptions::default(), FormatOptions { width: Some(40) }] {
+            let formatted = match format(&text, &options) {
+                Ok(formatted) => formatted,
+                Err(FormatError::Diagnostics(_)) => continue,
+                Err(err) => panic!("{err}"),
+            };
+            let again = format(&formatted, &options).unwrap();
+            if again != formatted {
+                failures.push(format!(
+                    "{} ({options:?}): formatting again changes\n{formatted}\ninto\n{again}",
+                    fixture_name(root, &path)
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
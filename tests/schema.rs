@@ -0,0 +1,36 @@
+//! Generated JSON Schema and TypeScript definitions in `bindings/js/ast` match the rust types.
+//! Run with `UPDATE_SCHEMA=1` to regenerate them after changing the AST.
+#![cfg(feature = "schema")]
+
+use std::{fs, path::Path};
+
+use norg_rs::schema;
+
+fn check(path: &str, generated: &str) -> Option<String> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    if std::env::var_os("UPDATE_SCHEMA").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, generated).unwrap();
+        return None;
+    }
+    let shipped = fs::read_to_string(&path).unwrap_or_default();
+    (shipped != generated).then(|| format!("{} is out of date", path.display()))
+}
+
+#[test]
+fn test_schema_up_to_date() {
+    let mut json = serde_json::to_string_pretty(&schema::json_schema()).unwrap();
+    json.push('\n');
+    let failures: Vec<_> = [
+        check("bindings/js/ast/norg-ast.schema.json", &json),
+        check("bindings/js/ast/norg-ast.d.ts", &schema::typescript()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    assert!(
+        failures.is_empty(),
+        "{}\nrerun with `UPDATE_SCHEMA=1` to regenerate",
+        failures.join("\n")
+    );
+}